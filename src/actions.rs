@@ -0,0 +1,472 @@
+use anyhow::{anyhow, Result};
+use bevy::{
+    input::{
+        gamepad::{
+            Gamepad, GamepadAxis, GamepadAxisType, GamepadButton, GamepadButtonType, GamepadEvent,
+            GamepadEventType,
+        },
+        Axis,
+    },
+    prelude::*,
+};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+
+pub const CONFIG_PATH: &str = "assets/input.ron";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    Jump,
+    Run,
+    Up,
+    Down,
+}
+
+impl Action {
+    pub fn all() -> &'static [Action; 6] {
+        &[
+            Action::MoveLeft,
+            Action::MoveRight,
+            Action::Jump,
+            Action::Run,
+            Action::Up,
+            Action::Down,
+        ]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum AxisDirection {
+    Positive,
+    Negative,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Binding {
+    Key(KeyCode),
+    Button(GamepadButtonType),
+    /// one half of a stick axis
+    Axis(GamepadAxisType, AxisDirection),
+}
+
+/// bindings as written in the config file, keys and buttons by name
+#[derive(Debug, Deserialize)]
+enum BindingConfig {
+    Key(String),
+    Button(String),
+    Axis(String, AxisDirection),
+}
+
+#[derive(Debug, Deserialize)]
+struct Config {
+    dead_zone: f32,
+    bindings: HashMap<Action, Vec<BindingConfig>>,
+}
+
+/// action bindings, filled from the config file and changeable at runtime
+pub struct InputMap {
+    bindings: HashMap<Action, Vec<Binding>>,
+    /// axis values below this count as zero
+    pub dead_zone: f32,
+    /// the next key / button / stick movement gets bound to this action
+    rebinding: Option<Action>,
+}
+
+impl Default for InputMap {
+    fn default() -> Self {
+        let mut map = InputMap {
+            bindings: HashMap::new(),
+            dead_zone: 0.25,
+            rebinding: None,
+        };
+        map.bind(Action::MoveLeft, Binding::Key(KeyCode::A));
+        map.bind(Action::MoveRight, Binding::Key(KeyCode::D));
+        map.bind(Action::Jump, Binding::Key(KeyCode::RControl));
+        map.bind(Action::Run, Binding::Key(KeyCode::LShift));
+        map.bind(Action::Up, Binding::Key(KeyCode::W));
+        map.bind(Action::Down, Binding::Key(KeyCode::S));
+        map
+    }
+}
+
+impl InputMap {
+    pub fn from_bytes(bytes: &[u8]) -> Result<InputMap> {
+        let config: Config = ron::de::from_bytes(bytes)?;
+        let mut map = InputMap {
+            bindings: HashMap::new(),
+            dead_zone: config.dead_zone,
+            rebinding: None,
+        };
+        for (action, bindings) in config.bindings.iter() {
+            for binding in bindings.iter() {
+                let binding = match binding {
+                    BindingConfig::Key(name) => Binding::Key(
+                        key_code(name).ok_or_else(|| anyhow!("unknown key '{}'", name))?,
+                    ),
+                    BindingConfig::Button(name) => Binding::Button(
+                        button_type(name).ok_or_else(|| anyhow!("unknown button '{}'", name))?,
+                    ),
+                    BindingConfig::Axis(name, direction) => Binding::Axis(
+                        axis_type(name).ok_or_else(|| anyhow!("unknown axis '{}'", name))?,
+                        *direction,
+                    ),
+                };
+                map.bindings.entry(*action).or_default().push(binding);
+            }
+        }
+        Ok(map)
+    }
+
+    /// the config file, or the default bindings if it cannot be read
+    pub fn load(path: &str) -> InputMap {
+        match std::fs::read(path)
+            .map_err(anyhow::Error::from)
+            .and_then(|bytes| InputMap::from_bytes(&bytes))
+        {
+            Ok(map) => map,
+            Err(err) => {
+                log::warn!("failed to load input config {}: {}", path, err);
+                InputMap::default()
+            }
+        }
+    }
+
+    pub fn bindings(&self, action: Action) -> &[Binding] {
+        self.bindings.get(&action).map_or(&[], |b| &b[..])
+    }
+
+    /// adds a binding to the action, taking it away from other actions
+    pub fn bind(&mut self, action: Action, binding: Binding) {
+        for bindings in self.bindings.values_mut() {
+            bindings.retain(|b| *b != binding);
+        }
+        self.bindings.entry(action).or_default().push(binding);
+    }
+
+    pub fn clear(&mut self, action: Action) {
+        self.bindings.remove(&action);
+    }
+
+    /// binds the next pressed key, button or moved stick to the action (escape cancels)
+    pub fn start_rebind(&mut self, action: Action) {
+        self.rebinding = Some(action);
+    }
+
+    pub fn rebinding(&self) -> Option<Action> {
+        self.rebinding
+    }
+}
+
+/// current value of every action, 0 (released) to 1 (fully pressed)
+#[derive(Default)]
+pub struct Actions {
+    values: HashMap<Action, f32>,
+}
+
+impl Actions {
+    pub fn value(&self, action: Action) -> f32 {
+        self.values.get(&action).cloned().unwrap_or(0.0)
+    }
+
+    pub fn pressed(&self, action: Action) -> bool {
+        self.value(action) > 0.0
+    }
+}
+
+/// strength of one direction of an axis, rescaled so that it starts at 0 at the dead zone
+fn axis_value(value: f32, direction: AxisDirection, dead_zone: f32) -> f32 {
+    let value = match direction {
+        AxisDirection::Positive => value,
+        AxisDirection::Negative => -value,
+    };
+    if value <= dead_zone {
+        0.0
+    } else {
+        ((value - dead_zone) / (1.0 - dead_zone)).min(1.0)
+    }
+}
+
+#[derive(Default)]
+pub struct GamepadState {
+    events: EventReader<GamepadEvent>,
+    connected: HashSet<Gamepad>,
+}
+
+fn update_gamepads(state: &mut GamepadState, events: &Events<GamepadEvent>) {
+    for GamepadEvent(gamepad, event_type) in state.events.iter(events) {
+        match event_type {
+            GamepadEventType::Connected => {
+                state.connected.insert(*gamepad);
+            }
+            GamepadEventType::Disconnected => {
+                state.connected.remove(gamepad);
+            }
+            _ => (),
+        }
+    }
+}
+
+pub fn update_actions(
+    mut gamepads: Local<GamepadState>,
+    gamepad_events: Res<Events<GamepadEvent>>,
+    keyboard: Res<Input<KeyCode>>,
+    buttons: Res<Input<GamepadButton>>,
+    axes: Res<Axis<GamepadAxis>>,
+    map: Res<InputMap>,
+    mut actions: ResMut<Actions>,
+) {
+    update_gamepads(&mut gamepads, &gamepad_events);
+
+    for action in Action::all().iter() {
+        let value = map
+            .bindings(*action)
+            .iter()
+            .map(|binding| match binding {
+                Binding::Key(key) if keyboard.pressed(*key) => 1.0,
+                Binding::Key(_) => 0.0,
+                Binding::Button(button) => {
+                    let pressed = gamepads
+                        .connected
+                        .iter()
+                        .any(|gamepad| buttons.pressed(GamepadButton(*gamepad, *button)));
+                    if pressed {
+                        1.0
+                    } else {
+                        0.0
+                    }
+                }
+                Binding::Axis(axis, direction) => gamepads
+                    .connected
+                    .iter()
+                    .filter_map(|gamepad| axes.get(&GamepadAxis(*gamepad, *axis)))
+                    .map(|value| axis_value(value, *direction, map.dead_zone))
+                    .fold(0.0, f32::max),
+            })
+            .fold(0.0, f32::max);
+        actions.values.insert(*action, value);
+    }
+}
+
+/// captures the next input for InputMap::start_rebind
+pub fn rebind_actions(
+    mut gamepads: Local<GamepadState>,
+    gamepad_events: Res<Events<GamepadEvent>>,
+    keyboard: Res<Input<KeyCode>>,
+    buttons: Res<Input<GamepadButton>>,
+    axes: Res<Axis<GamepadAxis>>,
+    mut map: ResMut<InputMap>,
+) {
+    update_gamepads(&mut gamepads, &gamepad_events);
+    let action = match map.rebinding {
+        Some(action) => action,
+        None => return,
+    };
+
+    let binding = if keyboard.just_pressed(KeyCode::Escape) {
+        map.rebinding = None;
+        return;
+    } else if let Some(key) = keyboard
+        .get_just_pressed()
+        .find(|key| !REBIND_KEYS.contains(key))
+    {
+        Some(Binding::Key(*key))
+    } else if let Some(GamepadButton(_, button)) = buttons.get_just_pressed().next() {
+        Some(Binding::Button(*button))
+    } else {
+        let mut moved = None;
+        for gamepad in gamepads.connected.iter() {
+            for axis in AXES.iter().map(|(_, axis)| *axis) {
+                let value = axes.get(&GamepadAxis(*gamepad, axis)).unwrap_or(0.0);
+                // ask for a clear movement, not just anything outside of the dead zone
+                if value.abs() > 0.5 {
+                    let direction = if value > 0.0 {
+                        AxisDirection::Positive
+                    } else {
+                        AxisDirection::Negative
+                    };
+                    moved = Some(Binding::Axis(axis, direction));
+                }
+            }
+        }
+        moved
+    };
+
+    if let Some(binding) = binding {
+        log::info!("bind {:?} to {:?}", binding, action);
+        map.bind(action, binding);
+        map.rebinding = None;
+    }
+}
+
+/// debug keys that start rebinding the action with the same index in Action::all()
+const REBIND_KEYS: [KeyCode; 6] = [
+    KeyCode::F1,
+    KeyCode::F2,
+    KeyCode::F3,
+    KeyCode::F4,
+    KeyCode::F5,
+    KeyCode::F6,
+];
+
+/// starts a rebind when one of the REBIND_KEYS is pressed
+pub fn rebind_hotkeys(keyboard: Res<Input<KeyCode>>, mut map: ResMut<InputMap>) {
+    for (key, action) in REBIND_KEYS.iter().zip(Action::all().iter()) {
+        if keyboard.just_pressed(*key) {
+            log::info!("press a key, button or stick to bind to {:?}", action);
+            map.start_rebind(*action);
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct ActionPlugin;
+
+impl Plugin for ActionPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_resource(InputMap::load(CONFIG_PATH))
+            .init_resource::<Actions>()
+            .add_system_to_stage(stage::PRE_UPDATE, rebind_actions.system())
+            // after rebind_actions, which would otherwise take the hotkey as the new binding
+            .add_system_to_stage(stage::PRE_UPDATE, rebind_hotkeys.system())
+            .add_system_to_stage(stage::PRE_UPDATE, update_actions.system());
+    }
+}
+
+const AXES: &[(&str, GamepadAxisType)] = &[
+    ("LeftStickX", GamepadAxisType::LeftStickX),
+    ("LeftStickY", GamepadAxisType::LeftStickY),
+    ("LeftZ", GamepadAxisType::LeftZ),
+    ("RightStickX", GamepadAxisType::RightStickX),
+    ("RightStickY", GamepadAxisType::RightStickY),
+    ("RightZ", GamepadAxisType::RightZ),
+    ("DPadX", GamepadAxisType::DPadX),
+    ("DPadY", GamepadAxisType::DPadY),
+];
+
+const BUTTONS: &[(&str, GamepadButtonType)] = &[
+    ("South", GamepadButtonType::South),
+    ("East", GamepadButtonType::East),
+    ("North", GamepadButtonType::North),
+    ("West", GamepadButtonType::West),
+    ("C", GamepadButtonType::C),
+    ("Z", GamepadButtonType::Z),
+    ("LeftTrigger", GamepadButtonType::LeftTrigger),
+    ("LeftTrigger2", GamepadButtonType::LeftTrigger2),
+    ("RightTrigger", GamepadButtonType::RightTrigger),
+    ("RightTrigger2", GamepadButtonType::RightTrigger2),
+    ("Select", GamepadButtonType::Select),
+    ("Start", GamepadButtonType::Start),
+    ("Mode", GamepadButtonType::Mode),
+    ("LeftThumb", GamepadButtonType::LeftThumb),
+    ("RightThumb", GamepadButtonType::RightThumb),
+    ("DPadUp", GamepadButtonType::DPadUp),
+    ("DPadDown", GamepadButtonType::DPadDown),
+    ("DPadLeft", GamepadButtonType::DPadLeft),
+    ("DPadRight", GamepadButtonType::DPadRight),
+];
+
+const KEYS: &[(&str, KeyCode)] = &[
+    ("A", KeyCode::A),
+    ("B", KeyCode::B),
+    ("C", KeyCode::C),
+    ("D", KeyCode::D),
+    ("E", KeyCode::E),
+    ("F", KeyCode::F),
+    ("G", KeyCode::G),
+    ("H", KeyCode::H),
+    ("I", KeyCode::I),
+    ("J", KeyCode::J),
+    ("K", KeyCode::K),
+    ("L", KeyCode::L),
+    ("M", KeyCode::M),
+    ("N", KeyCode::N),
+    ("O", KeyCode::O),
+    ("P", KeyCode::P),
+    ("Q", KeyCode::Q),
+    ("R", KeyCode::R),
+    ("S", KeyCode::S),
+    ("T", KeyCode::T),
+    ("U", KeyCode::U),
+    ("V", KeyCode::V),
+    ("W", KeyCode::W),
+    ("X", KeyCode::X),
+    ("Y", KeyCode::Y),
+    ("Z", KeyCode::Z),
+    ("Key0", KeyCode::Key0),
+    ("Key1", KeyCode::Key1),
+    ("Key2", KeyCode::Key2),
+    ("Key3", KeyCode::Key3),
+    ("Key4", KeyCode::Key4),
+    ("Key5", KeyCode::Key5),
+    ("Key6", KeyCode::Key6),
+    ("Key7", KeyCode::Key7),
+    ("Key8", KeyCode::Key8),
+    ("Key9", KeyCode::Key9),
+    ("Left", KeyCode::Left),
+    ("Right", KeyCode::Right),
+    ("Up", KeyCode::Up),
+    ("Down", KeyCode::Down),
+    ("Space", KeyCode::Space),
+    ("Return", KeyCode::Return),
+    ("Escape", KeyCode::Escape),
+    ("Tab", KeyCode::Tab),
+    ("Back", KeyCode::Back),
+    ("LShift", KeyCode::LShift),
+    ("RShift", KeyCode::RShift),
+    ("LControl", KeyCode::LControl),
+    ("RControl", KeyCode::RControl),
+    ("LAlt", KeyCode::LAlt),
+    ("RAlt", KeyCode::RAlt),
+];
+
+fn key_code(name: &str) -> Option<KeyCode> {
+    KEYS.iter().find(|(n, _)| *n == name).map(|(_, key)| *key)
+}
+
+fn button_type(name: &str) -> Option<GamepadButtonType> {
+    BUTTONS.iter().find(|(n, _)| *n == name).map(|(_, b)| *b)
+}
+
+fn axis_type(name: &str) -> Option<GamepadAxisType> {
+    AXES.iter().find(|(n, _)| *n == name).map(|(_, a)| *a)
+}
+
+#[test]
+fn test_input_config() {
+    let bytes = std::fs::read(CONFIG_PATH).unwrap();
+    let map = InputMap::from_bytes(&bytes).unwrap();
+    assert!(map
+        .bindings(Action::MoveLeft)
+        .contains(&Binding::Key(KeyCode::A)));
+    assert!(map.bindings(Action::MoveLeft).contains(&Binding::Axis(
+        GamepadAxisType::LeftStickX,
+        AxisDirection::Negative
+    )));
+    assert!(map
+        .bindings(Action::Jump)
+        .contains(&Binding::Button(GamepadButtonType::South)));
+
+    let unknown = br#"(dead_zone: 0.2, bindings: { Jump: [Key("Hyper")] })"#;
+    assert!(InputMap::from_bytes(unknown).is_err());
+}
+
+#[test]
+fn test_rebind() {
+    let mut map = InputMap::default();
+    map.bind(Action::Jump, Binding::Key(KeyCode::A));
+    assert_eq!(map.bindings(Action::Jump).len(), 2);
+    // a binding belongs to one action only
+    assert!(map.bindings(Action::MoveLeft).is_empty());
+    map.clear(Action::Jump);
+    assert!(map.bindings(Action::Jump).is_empty());
+}
+
+#[test]
+fn test_axis_dead_zone() {
+    assert_eq!(axis_value(0.2, AxisDirection::Positive, 0.25), 0.0);
+    assert_eq!(axis_value(-0.8, AxisDirection::Positive, 0.25), 0.0);
+    assert_relative_eq!(axis_value(-0.625, AxisDirection::Negative, 0.25), 0.5);
+    assert_relative_eq!(axis_value(1.0, AxisDirection::Positive, 0.25), 1.0);
+}
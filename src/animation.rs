@@ -0,0 +1,135 @@
+use super::spritesheet::{Animation, LoopMode};
+
+/// clock of a spritesheet frame tag, advanced by the caller
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AnimationPlayer {
+    tag: Option<String>,
+    /// position in the frames of the animation, None until the tag has been started
+    frame: Option<usize>,
+    /// milliseconds left of the current frame
+    time_left: f32,
+    /// pingpong is on its way back
+    backwards: bool,
+}
+
+impl AnimationPlayer {
+    /// starts playing the tag; playing the current tag again keeps its progress
+    pub fn play(&mut self, tag: &str) {
+        if self.tag.as_deref() == Some(tag) {
+            return;
+        }
+        self.tag = Some(tag.to_string());
+        self.frame = None;
+        self.backwards = false;
+    }
+
+    pub fn stop(&mut self) {
+        self.tag = None;
+        self.frame = None;
+    }
+
+    pub fn tag(&self) -> Option<&str> {
+        self.tag.as_deref()
+    }
+
+    /// advances the playing tag, whose frames are animation, by d_ms milliseconds and returns
+    /// the sprite index to show
+    pub fn advance(&mut self, animation: &Animation, d_ms: f32) -> u32 {
+        let mut frame = match self.frame {
+            Some(frame) if frame < animation.frames.len() => {
+                self.time_left -= d_ms;
+                frame
+            }
+            // not started yet or the spritesheet was reloaded with fewer frames
+            _ => {
+                let frame = self.start(animation);
+                self.time_left = animation.durations[frame].max(1) as f32;
+                frame
+            }
+        };
+        while self.time_left <= 0.0 {
+            frame = self.step(frame, animation);
+            self.time_left += animation.durations[frame].max(1) as f32;
+        }
+        self.frame = Some(frame);
+        animation.frames[frame]
+    }
+
+    fn start(&mut self, animation: &Animation) -> usize {
+        self.backwards = false;
+        match animation.loop_mode {
+            LoopMode::Reverse => animation.frames.len() - 1,
+            LoopMode::Forward | LoopMode::PingPong => 0,
+        }
+    }
+
+    fn step(&mut self, frame: usize, animation: &Animation) -> usize {
+        let (first, last) = (0, animation.frames.len() - 1);
+        match animation.loop_mode {
+            LoopMode::Forward if frame >= last => first,
+            LoopMode::Forward => frame + 1,
+            LoopMode::Reverse if frame <= first => last,
+            LoopMode::Reverse => frame - 1,
+            LoopMode::PingPong if first == last => first,
+            LoopMode::PingPong if self.backwards => {
+                if frame <= first {
+                    self.backwards = false;
+                    first + 1
+                } else {
+                    frame - 1
+                }
+            }
+            LoopMode::PingPong => {
+                if frame >= last {
+                    self.backwards = true;
+                    last - 1
+                } else {
+                    frame + 1
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_animation_step() {
+    let mut player = AnimationPlayer::default();
+    let mut sequence = |loop_mode| {
+        let animation = Animation {
+            frames: vec![2, 3, 4],
+            durations: vec![100; 3],
+            loop_mode,
+        };
+        let mut frame = player.start(&animation);
+        let mut frames = vec![animation.frames[frame]];
+        for _ in 0..6 {
+            frame = player.step(frame, &animation);
+            frames.push(animation.frames[frame]);
+        }
+        frames
+    };
+    assert_eq!(sequence(LoopMode::Forward), vec![2, 3, 4, 2, 3, 4, 2]);
+    assert_eq!(sequence(LoopMode::Reverse), vec![4, 3, 2, 4, 3, 2, 4]);
+    assert_eq!(sequence(LoopMode::PingPong), vec![2, 3, 4, 3, 2, 3, 4]);
+
+    let single = Animation {
+        frames: vec![8],
+        durations: vec![100],
+        loop_mode: LoopMode::PingPong,
+    };
+    let frame = player.start(&single);
+    assert_eq!(player.step(frame, &single), 0);
+
+    // time carries over between calls, whatever their length
+    let animation = Animation {
+        frames: vec![2, 3, 4],
+        durations: vec![100; 3],
+        loop_mode: LoopMode::Forward,
+    };
+    let mut player = AnimationPlayer::default();
+    player.play("walk");
+    assert_eq!(player.advance(&animation, 1000.0), 2);
+    assert_eq!(player.advance(&animation, 60.0), 2);
+    assert_eq!(player.advance(&animation, 60.0), 3);
+    assert_eq!(player.advance(&animation, 250.0), 2);
+}
@@ -0,0 +1,594 @@
+//! reader for the binary .aseprite / .ase format, see
+//! https://github.com/aseprite/aseprite/blob/master/docs/ase-file-specs.md
+use anyhow::{anyhow, Result};
+
+const HEADER_MAGIC: u16 = 0xa5e0;
+const FRAME_MAGIC: u16 = 0xf1fa;
+
+const CHUNK_OLD_PALETTE: u16 = 0x0004;
+const CHUNK_LAYER: u16 = 0x2004;
+const CHUNK_CEL: u16 = 0x2005;
+const CHUNK_TAGS: u16 = 0x2018;
+const CHUNK_PALETTE: u16 = 0x2019;
+const CHUNK_SLICE: u16 = 0x2022;
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Reader<'a> {
+        Reader { bytes, pos: 0 }
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        if self.pos + n > self.bytes.len() {
+            return Err(anyhow!(
+                "unexpected end of data at {} (reading {} bytes)",
+                self.pos,
+                n
+            ));
+        }
+        let bytes = &self.bytes[self.pos..self.pos + n];
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    fn rest(&mut self) -> &'a [u8] {
+        let bytes = &self.bytes[self.pos..];
+        self.pos = self.bytes.len();
+        bytes
+    }
+
+    fn skip(&mut self, n: usize) -> Result<()> {
+        self.take(n).map(|_| ())
+    }
+
+    fn byte(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn word(&mut self) -> Result<u16> {
+        let b = self.take(2)?;
+        Ok(u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    fn short(&mut self) -> Result<i16> {
+        Ok(self.word()? as i16)
+    }
+
+    fn dword(&mut self) -> Result<u32> {
+        let b = self.take(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn long(&mut self) -> Result<i32> {
+        Ok(self.dword()? as i32)
+    }
+
+    fn string(&mut self) -> Result<String> {
+        let len = self.word()? as usize;
+        Ok(String::from_utf8_lossy(self.take(len)?).into_owned())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorMode {
+    Rgba,
+    Grayscale,
+    Indexed,
+}
+
+impl ColorMode {
+    fn bytes_per_pixel(&self) -> usize {
+        match self {
+            ColorMode::Rgba => 4,
+            ColorMode::Grayscale => 2,
+            ColorMode::Indexed => 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Layer {
+    pub name: String,
+    pub visible: bool,
+    pub background: bool,
+    pub group: bool,
+    pub child_level: u16,
+    pub opacity: u8,
+}
+
+#[derive(Debug, Clone)]
+pub struct Tag {
+    pub name: String,
+    pub from: u16,
+    pub to: u16,
+    /// 0 forward, 1 reverse, 2 pingpong, 3 pingpong starting in reverse
+    pub direction: u8,
+}
+
+#[derive(Debug, Clone)]
+pub struct SliceKey {
+    pub frame: u32,
+    pub x: i32,
+    pub y: i32,
+    pub w: u32,
+    pub h: u32,
+    pub pivot: Option<(i32, i32)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Slice {
+    pub name: String,
+    pub keys: Vec<SliceKey>,
+}
+
+#[derive(Debug)]
+enum CelData {
+    /// pixels in the color mode of the file
+    Image { w: u16, h: u16, pixels: Vec<u8> },
+    /// same image as the cel of the layer in the given frame
+    Linked(u16),
+}
+
+#[derive(Debug)]
+struct Cel {
+    layer: u16,
+    x: i16,
+    y: i16,
+    opacity: u8,
+    z_index: i16,
+    data: CelData,
+}
+
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub duration: u32,
+    /// composited RGBA pixels, width * height
+    pub pixels: Vec<u8>,
+}
+
+#[derive(Debug)]
+pub struct AseFile {
+    pub width: u32,
+    pub height: u32,
+    pub color_mode: ColorMode,
+    pub layers: Vec<Layer>,
+    pub tags: Vec<Tag>,
+    pub slices: Vec<Slice>,
+    pub frames: Vec<Frame>,
+}
+
+impl AseFile {
+    pub fn parse(bytes: &[u8]) -> Result<AseFile> {
+        let mut r = Reader::new(bytes);
+        let _file_size = r.dword()?;
+        let magic = r.word()?;
+        if magic != HEADER_MAGIC {
+            return Err(anyhow!("not an aseprite file (magic {:#06x})", magic));
+        }
+        let num_frames = r.word()?;
+        let width = r.word()? as u32;
+        let height = r.word()? as u32;
+        let color_mode = match r.word()? {
+            32 => ColorMode::Rgba,
+            16 => ColorMode::Grayscale,
+            8 => ColorMode::Indexed,
+            depth => return Err(anyhow!("unsupported color depth {}", depth)),
+        };
+        let flags = r.dword()?;
+        let layer_opacity_valid = flags & 1 != 0;
+        r.skip(2 + 4 + 4)?;
+        let transparent_index = r.byte()?;
+        r.skip(3 + 2 + 1 + 1 + 2 + 2 + 2 + 2 + 84)?;
+
+        let mut layers = Vec::new();
+        let mut tags = Vec::new();
+        let mut slices = Vec::new();
+        let mut palette = vec![[0u8; 4]; 256];
+        let mut has_new_palette = false;
+        let mut frame_cels = Vec::new();
+        let mut durations = Vec::new();
+
+        for frame in 0..num_frames {
+            let frame_size = r.dword()? as usize;
+            let mut fr = Reader::new(
+                r.take(frame_size.saturating_sub(4))
+                    .map_err(|e| anyhow!("frame {}: {}", frame, e))?,
+            );
+            let magic = fr.word()?;
+            if magic != FRAME_MAGIC {
+                return Err(anyhow!("frame {}: bad magic {:#06x}", frame, magic));
+            }
+            let old_chunks = fr.word()? as u32;
+            let duration = fr.word()? as u32;
+            fr.skip(2)?;
+            let new_chunks = fr.dword()?;
+            let num_chunks = if new_chunks == 0 {
+                old_chunks
+            } else {
+                new_chunks
+            };
+
+            let mut cels = Vec::new();
+            for _ in 0..num_chunks {
+                let chunk_size = fr.dword()? as usize;
+                let chunk_type = fr.word()?;
+                let mut cr =
+                    Reader::new(fr.take(chunk_size.saturating_sub(6)).map_err(|e| {
+                        anyhow!("frame {} chunk {:#06x}: {}", frame, chunk_type, e)
+                    })?);
+                let res = match chunk_type {
+                    CHUNK_LAYER => read_layer(&mut cr, layer_opacity_valid).map(|l| layers.push(l)),
+                    CHUNK_CEL => read_cel(&mut cr, color_mode).map(|c| cels.push(c)),
+                    CHUNK_TAGS => read_tags(&mut cr).map(|t| tags = t),
+                    CHUNK_SLICE => read_slice(&mut cr).map(|s| slices.push(s)),
+                    CHUNK_PALETTE => {
+                        has_new_palette = true;
+                        read_palette(&mut cr, &mut palette)
+                    }
+                    CHUNK_OLD_PALETTE if !has_new_palette => {
+                        read_old_palette(&mut cr, &mut palette)
+                    }
+                    // color profile, user data, ...
+                    _ => Ok(()),
+                };
+                res.map_err(|e| anyhow!("frame {} chunk {:#06x}: {}", frame, chunk_type, e))?;
+            }
+            frame_cels.push(cels);
+            durations.push(duration);
+        }
+
+        let visible = effective_visibility(&layers);
+        let mut frames = Vec::new();
+        for (frame, cels) in frame_cels.iter().enumerate() {
+            let mut pixels = vec![0u8; (width * height * 4) as usize];
+            let mut order: Vec<&Cel> = cels.iter().collect();
+            order.sort_by_key(|c| (c.layer as i32 + c.z_index as i32, c.z_index));
+            for cel in order {
+                let layer = match layers.get(cel.layer as usize) {
+                    Some(layer) => layer,
+                    None => return Err(anyhow!("frame {}: unknown layer {}", frame, cel.layer)),
+                };
+                if !visible[cel.layer as usize] {
+                    continue;
+                }
+                let (w, h, cel_pixels) = match cel.data {
+                    CelData::Image { w, h, ref pixels } => (w, h, pixels),
+                    CelData::Linked(link) => {
+                        match find_image(&frame_cels, link as usize, cel.layer) {
+                            Some(image) => image,
+                            None => {
+                                return Err(anyhow!(
+                                    "frame {}: broken link to frame {} in layer {}",
+                                    frame,
+                                    link,
+                                    cel.layer
+                                ))
+                            }
+                        }
+                    }
+                };
+                let opacity = (cel.opacity as u32 * layer.opacity as u32 / 255) as u8;
+                let bpp = color_mode.bytes_per_pixel();
+                for cy in 0..h as i32 {
+                    for cx in 0..w as i32 {
+                        let (x, y) = (cel.x as i32 + cx, cel.y as i32 + cy);
+                        if x < 0 || y < 0 || x >= width as i32 || y >= height as i32 {
+                            continue;
+                        }
+                        let src = &cel_pixels[(cy as usize * w as usize + cx as usize) * bpp..];
+                        let src = match color_mode {
+                            ColorMode::Rgba => [src[0], src[1], src[2], src[3]],
+                            ColorMode::Grayscale => [src[0], src[0], src[0], src[1]],
+                            ColorMode::Indexed
+                                if src[0] == transparent_index && !layer.background =>
+                            {
+                                [0, 0, 0, 0]
+                            }
+                            ColorMode::Indexed => palette[src[0] as usize],
+                        };
+                        let dst = (y as usize * width as usize + x as usize) * 4;
+                        blend_normal(&mut pixels[dst..dst + 4], src, opacity);
+                    }
+                }
+            }
+            frames.push(Frame {
+                duration: durations[frame],
+                pixels,
+            });
+        }
+
+        Ok(AseFile {
+            width,
+            height,
+            color_mode,
+            layers,
+            tags,
+            slices,
+            frames,
+        })
+    }
+
+    /// all frames next to each other in one RGBA image of (width * frames) x height
+    pub fn sheet_pixels(&self) -> Vec<u8> {
+        let row = self.width as usize * 4;
+        let mut pixels = Vec::with_capacity(row * self.height as usize * self.frames.len());
+        for y in 0..self.height as usize {
+            for frame in self.frames.iter() {
+                pixels.extend_from_slice(&frame.pixels[y * row..(y + 1) * row]);
+            }
+        }
+        pixels
+    }
+}
+
+fn read_layer(r: &mut Reader, opacity_valid: bool) -> Result<Layer> {
+    let flags = r.word()?;
+    let layer_type = r.word()?;
+    let child_level = r.word()?;
+    r.skip(2 + 2)?;
+    // other blend modes are drawn as normal
+    let _blend_mode = r.word()?;
+    let opacity = r.byte()?;
+    r.skip(3)?;
+    let name = r.string()?;
+    Ok(Layer {
+        name,
+        visible: flags & 1 != 0,
+        background: flags & 8 != 0,
+        group: layer_type == 1,
+        child_level,
+        opacity: if opacity_valid { opacity } else { 255 },
+    })
+}
+
+fn read_cel(r: &mut Reader, color_mode: ColorMode) -> Result<Cel> {
+    let layer = r.word()?;
+    let x = r.short()?;
+    let y = r.short()?;
+    let opacity = r.byte()?;
+    let cel_type = r.word()?;
+    let z_index = r.short()?;
+    r.skip(5)?;
+    let data = match cel_type {
+        0 | 2 => {
+            let w = r.word()?;
+            let h = r.word()?;
+            let pixels = if cel_type == 0 {
+                r.rest().to_vec()
+            } else {
+                miniz_oxide::inflate::decompress_to_vec_zlib(r.rest())
+                    .map_err(|e| anyhow!("cel of layer {}: zlib error {:?}", layer, e))?
+            };
+            let len = w as usize * h as usize * color_mode.bytes_per_pixel();
+            if pixels.len() < len {
+                return Err(anyhow!(
+                    "cel of layer {}: {} bytes of pixels, {} expected",
+                    layer,
+                    pixels.len(),
+                    len
+                ));
+            }
+            CelData::Image { w, h, pixels }
+        }
+        1 => CelData::Linked(r.word()?),
+        _ => return Err(anyhow!("unsupported cel type {}", cel_type)),
+    };
+    Ok(Cel {
+        layer,
+        x,
+        y,
+        opacity,
+        z_index,
+        data,
+    })
+}
+
+fn read_tags(r: &mut Reader) -> Result<Vec<Tag>> {
+    let num_tags = r.word()?;
+    r.skip(8)?;
+    let mut tags = Vec::new();
+    for _ in 0..num_tags {
+        let from = r.word()?;
+        let to = r.word()?;
+        let direction = r.byte()?;
+        r.skip(2 + 6 + 3 + 1)?;
+        let name = r.string()?;
+        tags.push(Tag {
+            name,
+            from,
+            to,
+            direction,
+        });
+    }
+    Ok(tags)
+}
+
+fn read_slice(r: &mut Reader) -> Result<Slice> {
+    let num_keys = r.dword()?;
+    let flags = r.dword()?;
+    r.skip(4)?;
+    let name = r.string()?;
+    let mut keys = Vec::new();
+    for _ in 0..num_keys {
+        let frame = r.dword()?;
+        let x = r.long()?;
+        let y = r.long()?;
+        let w = r.dword()?;
+        let h = r.dword()?;
+        if flags & 1 != 0 {
+            // 9-patch center
+            r.skip(16)?;
+        }
+        let pivot = if flags & 2 != 0 {
+            Some((r.long()?, r.long()?))
+        } else {
+            None
+        };
+        keys.push(SliceKey {
+            frame,
+            x,
+            y,
+            w,
+            h,
+            pivot,
+        });
+    }
+    Ok(Slice { name, keys })
+}
+
+fn read_palette(r: &mut Reader, palette: &mut [[u8; 4]]) -> Result<()> {
+    let _size = r.dword()?;
+    let first = r.dword()? as usize;
+    let last = r.dword()? as usize;
+    r.skip(8)?;
+    for i in first..=last {
+        let flags = r.word()?;
+        let color = [r.byte()?, r.byte()?, r.byte()?, r.byte()?];
+        if flags & 1 != 0 {
+            r.string()?;
+        }
+        if i < palette.len() {
+            palette[i] = color;
+        }
+    }
+    Ok(())
+}
+
+fn read_old_palette(r: &mut Reader, palette: &mut [[u8; 4]]) -> Result<()> {
+    let packets = r.word()?;
+    let mut i = 0usize;
+    for _ in 0..packets {
+        i += r.byte()? as usize;
+        let count = match r.byte()? {
+            0 => 256,
+            n => n as usize,
+        };
+        for _ in 0..count {
+            let color = [r.byte()?, r.byte()?, r.byte()?, 255];
+            if i < palette.len() {
+                palette[i] = color;
+            }
+            i += 1;
+        }
+    }
+    Ok(())
+}
+
+/// layers in hidden groups are hidden as well
+fn effective_visibility(layers: &[Layer]) -> Vec<bool> {
+    let mut parents: Vec<bool> = Vec::new();
+    layers
+        .iter()
+        .map(|layer| {
+            let level = layer.child_level as usize;
+            parents.truncate(level);
+            let visible = layer.visible && parents.iter().all(|v| *v);
+            parents.push(visible);
+            visible
+        })
+        .collect()
+}
+
+fn find_image(frame_cels: &[Vec<Cel>], frame: usize, layer: u16) -> Option<(u16, u16, &Vec<u8>)> {
+    frame_cels
+        .get(frame)?
+        .iter()
+        .filter(|c| c.layer == layer)
+        .find_map(|c| match c.data {
+            CelData::Image { w, h, ref pixels } => Some((w, h, pixels)),
+            CelData::Linked(_) => None,
+        })
+}
+
+/// alpha compositing of straight (non premultiplied) colors
+fn blend_normal(dst: &mut [u8], src: [u8; 4], opacity: u8) {
+    let sa = src[3] as u32 * opacity as u32 / 255;
+    if sa == 0 {
+        return;
+    }
+    let da = dst[3] as u32 * (255 - sa) / 255;
+    let a = sa + da;
+    for c in 0..3 {
+        dst[c] = ((src[c] as u32 * sa + dst[c] as u32 * da) / a) as u8;
+    }
+    dst[3] = a as u8;
+}
+
+#[test]
+fn test_parse_ferris() {
+    let bytes = std::fs::read("assets/ferris2.0.aseprite").unwrap();
+    let ase = AseFile::parse(&bytes).unwrap();
+    assert_eq!((ase.width, ase.height), (16, 16));
+    assert_eq!(ase.color_mode, ColorMode::Rgba);
+    let names: Vec<&str> = ase.layers.iter().map(|l| l.name.as_str()).collect();
+    assert_eq!(names, vec!["body", "pincers", "legs", "face"]);
+    assert_eq!(ase.frames.len(), 14);
+    assert!(ase.frames.iter().all(|f| f.duration == 100));
+    assert_eq!(ase.tags.len(), 8);
+    assert_eq!(ase.tags[0].name, "walk left");
+    assert_eq!((ase.tags[1].from, ase.tags[1].to), (4, 7));
+    assert_eq!(ase.tags[2].name, "jump left");
+    assert_eq!(ase.tags[7].name, "wall jump right");
+    assert_eq!((ase.tags[7].from, ase.tags[7].to), (13, 13));
+
+    // every frame shows something, frame 1 uses linked cels of frame 0, the wall frames of
+    // the jump frames
+    for frame in ase.frames.iter() {
+        assert!(frame.pixels.chunks(4).any(|p| p[3] == 255));
+    }
+    assert_eq!(ase.sheet_pixels().len(), 16 * 14 * 16 * 4);
+}
+
+#[test]
+fn test_parse_indexed() {
+    fn chunk(chunk_type: u16, data: &[u8]) -> Vec<u8> {
+        let mut c = ((data.len() + 6) as u32).to_le_bytes().to_vec();
+        c.extend_from_slice(&chunk_type.to_le_bytes());
+        c.extend_from_slice(data);
+        c
+    }
+    // palette: 0 transparent, 1 red, 2 blue
+    let mut palette = vec![3, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    for color in [[0u8, 0, 0, 0], [255, 0, 0, 255], [0, 0, 255, 255]].iter() {
+        palette.extend_from_slice(&[0, 0]);
+        palette.extend_from_slice(color);
+    }
+    let mut layer = vec![1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 255, 0, 0, 0];
+    layer.extend_from_slice(&[2, 0, b'b', b'g']);
+    // raw 2x1 cel at (1, 1)
+    let cel = vec![
+        0, 0, 1, 0, 1, 0, 255, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 0, 1, 0, 1, 0,
+    ];
+    let chunks = [
+        chunk(CHUNK_PALETTE, &palette),
+        chunk(CHUNK_LAYER, &layer),
+        chunk(CHUNK_CEL, &cel),
+    ]
+    .concat();
+
+    let mut frame = ((chunks.len() + 16) as u32).to_le_bytes().to_vec();
+    frame.extend_from_slice(&[0xfa, 0xf1, 3, 0, 50, 0, 0, 0, 3, 0, 0, 0]);
+    frame.extend_from_slice(&chunks);
+
+    let mut file = vec![0u8; 128];
+    file[4..6].copy_from_slice(&HEADER_MAGIC.to_le_bytes());
+    file[6] = 1; // frames
+    file[8] = 3; // width
+    file[10] = 2; // height
+    file[12] = 8; // color depth
+    file[14] = 1; // layer opacity valid
+    file.extend_from_slice(&frame);
+
+    let ase = AseFile::parse(&file).unwrap();
+    assert_eq!(ase.color_mode, ColorMode::Indexed);
+    assert_eq!(ase.frames[0].duration, 50);
+    let pixels = &ase.frames[0].pixels;
+    // first row empty, red pixel at (1, 1), transparent index 0 at (2, 1)
+    assert!(pixels[..12].iter().all(|p| *p == 0));
+    assert_eq!(&pixels[16..20], &[255, 0, 0, 255]);
+    assert_eq!(&pixels[20..24], &[0, 0, 0, 0]);
+}
@@ -0,0 +1,224 @@
+use anyhow::{anyhow, Result};
+use bevy::{
+    asset::{AssetLoader, LoadedAsset},
+    prelude::*,
+};
+use bevy_type_registry::TypeUuid;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// names of the states the state machine switches to. climb, wallslide and walljump are
+/// optional, they are only entered when the definition has them
+pub const STAND: &str = "stand";
+pub const WALK: &str = "walk";
+pub const JUMP: &str = "jump";
+pub const WALLSLIDE: &str = "wallslide";
+pub const WALLJUMP: &str = "walljump";
+pub const CLIMB: &str = "climb";
+
+/// (sprite_left, sprite_right, move_x, duration_ms, next_frame)
+#[derive(Default, Debug, Clone, Deserialize)]
+pub struct Frame(pub i32, pub i32, pub i32, pub i32, pub u32);
+
+#[derive(Debug, Clone, Deserialize)]
+pub enum Think {
+    Walk,
+    Air,
+    Stand,
+    /// pressing into a wall in the air, descending slowly
+    WallSlide,
+    /// pushed away from a wall, horizontal input is locked for a moment
+    WallJump,
+    /// moves up and down on a ladder without gravity
+    Climb,
+}
+
+impl Default for Think {
+    fn default() -> Self {
+        Think::Walk
+    }
+}
+
+impl Think {
+    /// required states this think can switch to
+    pub fn targets(&self) -> &'static [&'static str] {
+        match self {
+            Think::Walk => &[JUMP, STAND],
+            Think::Stand => &[JUMP, WALK],
+            Think::Air | Think::WallSlide | Think::WallJump | Think::Climb => &[JUMP],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub enum React {
+    Walk,
+    Air,
+    Stand,
+    /// leaves the state when the wall ends or the feet touch ground
+    WallSlide,
+    /// steps off at the top of the ladder, on the ground or falls when the ladder ends
+    Climb,
+}
+
+impl Default for React {
+    fn default() -> Self {
+        React::Walk
+    }
+}
+
+impl React {
+    /// required states this react can switch to
+    pub fn targets(&self) -> &'static [&'static str] {
+        match self {
+            React::Walk | React::Stand => &[JUMP],
+            // landing walks on
+            React::Air => &[WALK],
+            React::WallSlide => &[WALK, JUMP],
+            React::Climb => &[STAND, JUMP],
+        }
+    }
+}
+
+/// spritesheet frame tags played while facing left / right
+#[derive(Default, Debug, Clone, Deserialize)]
+pub struct AnimationTags {
+    pub left: String,
+    pub right: String,
+}
+
+#[derive(Default, Debug, Clone, Deserialize)]
+pub struct StateComplex {
+    pub frames: Vec<Frame>,
+    pub think: Think,
+    pub react: React,
+    /// without tags the sprite indices of the frames are shown
+    #[serde(default)]
+    pub animation: Option<AnimationTags>,
+}
+
+/// state machine of a character, loaded from a RON `.character` file
+#[derive(Debug, Deserialize, TypeUuid)]
+#[uuid = "f81c5b0f-e05f-496f-b69b-514f0c362029"]
+pub struct CharacterDef {
+    pub initial: String,
+    pub states: HashMap<String, StateComplex>,
+}
+
+impl CharacterDef {
+    pub fn from_bytes(bytes: &[u8]) -> Result<CharacterDef> {
+        let def: CharacterDef = ron::de::from_bytes(bytes)?;
+        def.validate()?;
+        Ok(def)
+    }
+
+    pub fn state(&self, name: &str) -> Option<&StateComplex> {
+        self.states.get(name)
+    }
+
+    /// the state machine code indexes frames directly, so catch broken definitions at load time
+    fn validate(&self) -> Result<()> {
+        if !self.states.contains_key(&self.initial) {
+            return Err(anyhow!("initial state '{}' is not defined", self.initial));
+        }
+        for (name, state) in self.states.iter() {
+            if state.frames.is_empty() {
+                return Err(anyhow!("state '{}' has no frames", name));
+            }
+            for (i, frame) in state.frames.iter().enumerate() {
+                if frame.3 <= 0 {
+                    return Err(anyhow!(
+                        "state '{}' frame {}: duration must be > 0",
+                        name,
+                        i
+                    ));
+                }
+                if frame.4 as usize >= state.frames.len() {
+                    return Err(anyhow!(
+                        "state '{}' frame {}: next frame {} out of range",
+                        name,
+                        i,
+                        frame.4
+                    ));
+                }
+            }
+            let mut targets = state.think.targets().iter().chain(state.react.targets());
+            if let Some(target) = targets.find(|t| !self.states.contains_key(**t)) {
+                return Err(anyhow!(
+                    "state '{}' switches to state '{}', which is not defined",
+                    name,
+                    target
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct CharacterDefLoader {}
+
+#[derive(Default)]
+pub struct CharacterPlugin;
+
+impl Plugin for CharacterPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_asset::<CharacterDef>()
+            .init_asset_loader::<CharacterDefLoader>();
+    }
+}
+
+impl AssetLoader for CharacterDefLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut bevy::asset::LoadContext,
+    ) -> bevy::utils::BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let def = CharacterDef::from_bytes(bytes)
+                .map_err(|e| anyhow!("{}: {}", load_context.path().display(), e))?;
+            load_context.set_default_asset(LoadedAsset::new(def));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        static EXTENSIONS: &[&str] = &["character"];
+        EXTENSIONS
+    }
+}
+
+#[test]
+fn test_load_character_def() {
+    let bytes = std::fs::read("assets/ferris.character").unwrap();
+    let def = CharacterDef::from_bytes(&bytes).unwrap();
+    assert_eq!(def.initial, "jump");
+    assert_eq!(def.state("walk").unwrap().frames.len(), 4);
+    assert_eq!(def.state("stand").unwrap().frames[0].1, 4);
+    assert!(def.state("fly").is_none());
+    assert!(matches!(
+        def.state("wallslide").unwrap().think,
+        Think::WallSlide
+    ));
+    let tags = def.state("walk").unwrap().animation.as_ref().unwrap();
+    assert_eq!(tags.left, "walk left");
+    assert!(def.state("stand").unwrap().animation.is_none());
+
+    let broken = br#"(
+        initial: "stand",
+        states: { "stand": (frames: [Frame(0, 4, 0, 100, 1)], think: Stand, react: Stand) },
+    )"#;
+    assert!(CharacterDef::from_bytes(broken).is_err());
+    let missing_initial = br#"(
+        initial: "walk",
+        states: { "stand": (frames: [Frame(0, 4, 0, 100, 0)], think: Stand, react: Stand) },
+    )"#;
+    assert!(CharacterDef::from_bytes(missing_initial).is_err());
+    // standing needs walk and jump to go on
+    let missing_target = br#"(
+        initial: "stand",
+        states: { "stand": (frames: [Frame(0, 4, 0, 100, 0)], think: Stand, react: Stand) },
+    )"#;
+    let err = CharacterDef::from_bytes(missing_target).unwrap_err();
+    assert!(err.to_string().contains("'jump'"));
+}
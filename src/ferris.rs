@@ -1,42 +1,19 @@
 use bevy::math;
 use bevy::{prelude::*, render::camera::Camera};
 // use bevy_tiled_prototype::level;
-use super::{level, movement, spritesheet};
+use super::{
+    actions::{Action, Actions},
+    animation::AnimationPlayer,
+    character::{self, CharacterDef, Frame, React, StateComplex, Think},
+    hitbox::{CharacterBoxes, FrameBoxes, Health},
+    level, movement,
+    replay::Replay,
+    spritesheet,
+    timestep::FixedTimestep,
+};
 use bevy_tiled_prototype::TiledMapCenter;
 
-pub fn animate_character_system(
-    texture_atlases: Res<Assets<TextureAtlas>>,
-    spritesheet_assets: Res<Assets<spritesheet::Spritesheet>>,
-    mut query: Query<(
-        &mut Timer,
-        &mut TextureAtlasSprite,
-        &Handle<TextureAtlas>,
-        &mut CharacterState,
-        &Handle<spritesheet::Spritesheet>,
-    )>,
-) {
-    for (timer, mut sprite, texture_atlas_handle, mut state, sprite_sheet) in query.iter_mut() {
-        let sprite_sheet = spritesheet_assets.get(sprite_sheet);
-
-        if sprite_sheet.is_none() {
-            continue;
-        }
-        let sprite_sheet = sprite_sheet.unwrap();
-        for (name, range) in sprite_sheet.ranges.iter() {
-            println!("{}: {:?}", name, range);
-        }
-        // sprite_sheet.
-
-        let frame = &state.state.frames[state.state_step as usize];
-        sprite.index = match state.face_dir {
-            Direction::West => frame.0,
-            Direction::East => frame.1,
-            _ => 0,
-        } as u32;
-    }
-}
-
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 enum Direction {
     East,
     West,
@@ -77,286 +54,867 @@ impl Direction {
     }
 }
 
-#[derive(Default, Debug)]
-struct Frame(i32, i32, i32, i32, u32);
-
-#[derive(Debug)]
-enum Think {
-    Walk,
-    Air,
-    Stand,
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+enum Movement {
+    Up,
+    Down,
+    Left,
+    Right,
 }
-
-impl Default for Think {
-    fn default() -> Self {
-        Think::Walk
-    }
+/// the input the simulation sees during one tick
+#[derive(Default, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InputState {
+    xaxis: Option<Movement>,
+    yaxis: Option<Movement>,
+    jump: bool,
+    /// jump went down since the last simulation step
+    jump_pressed: bool,
+    /// moves faster by CharacterParams::run_speed
+    run: bool,
 }
 
-#[derive(Debug)]
-enum React {
-    Walk,
-    Air,
-    Stand,
+#[derive(Debug, Clone)]
+pub struct CharacterParams {
+    /// steepest walkable slope in radians, steeper surfaces are not treated as ground
+    pub max_slope_angle: f32,
+    /// how far the feet follow the ground upwards while walking
+    pub step_up: f32,
+    /// how far the feet snap down to the ground while walking (e.g. descending slopes)
+    pub snap_down: f32,
+    /// downwards acceleration in the air, pixels per second squared
+    pub gravity: f32,
+    /// terminal speed of falling
+    pub max_fall_speed: f32,
+    /// upwards speed at the start of a jump
+    pub jump_speed: f32,
+    /// releasing jump while rising caps the upwards speed to this, shortening the jump
+    pub jump_release_speed: f32,
+    /// horizontal speed of a jump started while walking
+    pub run_jump_speed: f32,
+    /// seconds after walking off a ledge in which jumping is still possible
+    pub coyote_time: f32,
+    /// seconds a jump pressed in the air is remembered to be performed on landing
+    pub jump_buffer: f32,
+    /// fastest descent while sliding down a wall
+    pub wall_slide_speed: f32,
+    /// speed away from the wall and upwards at the start of a wall jump
+    pub wall_jump_speed: Vec2,
+    /// seconds horizontal input is ignored after a wall jump
+    pub wall_jump_lock: f32,
+    /// speed up and down ladders
+    pub climb_speed: f32,
+    /// factor on the walking and air speed while run is held
+    pub run_speed: f32,
 }
 
-impl Default for React {
+impl Default for CharacterParams {
     fn default() -> Self {
-        React::Walk
+        CharacterParams {
+            max_slope_angle: 50f32.to_radians(),
+            step_up: 6.0,
+            snap_down: 6.0,
+            gravity: 600.0,
+            max_fall_speed: 100.0,
+            jump_speed: 200.0,
+            jump_release_speed: 60.0,
+            run_jump_speed: 32.0,
+            coyote_time: 0.1,
+            jump_buffer: 0.1,
+            wall_slide_speed: 30.0,
+            wall_jump_speed: Vec2::new(48.0, 180.0),
+            wall_jump_lock: 0.15,
+            climb_speed: 40.0,
+            run_speed: 1.5,
+        }
     }
 }
 
-#[derive(Default, Debug)]
-struct StateComplex {
-    frames: &'static [Frame],
-    think: Think,
-    react: React,
+/// collision hull of the character; it sits step_up above the foot sensor so that slopes are
+/// handled by the feet alone (a hull narrower than step_up keeps it clear of slopes up to ~50
+/// degrees)
+fn body_rect(pixel_coord: Vec2, boxes: &FrameBoxes, params: &CharacterParams) -> math::Rect<f32> {
+    let body = movement::offset_rect(&boxes.body, &pixel_coord);
+    math::Rect {
+        bottom: pixel_coord.y() + boxes.foot.y() + params.step_up,
+        ..body
+    }
 }
 
-const FERRIS_STAND: StateComplex = StateComplex {
-    frames: &[Frame(0, 4, 0, 100, 0)],
-    think: Think::Stand,
-    react: React::Stand,
-};
+/// horizontal movement on the ground: lift the hull by step_up, move and put it down again, so
+/// that walkable slopes and small steps do not block the hull
+fn walk_move(
+    level: &level::Level,
+    body: &math::Rect<f32>,
+    movex: f32,
+    step_up: f32,
+) -> movement::MoveResult {
+    let lift = movement::move_and_slide(level, body, &Vec2::new(0.0, step_up));
+    let body = movement::offset_rect(body, &lift.delta);
+    let walk = movement::move_and_slide(level, &body, &Vec2::new(movex, 0.0));
+    let body = movement::offset_rect(&body, &walk.delta);
+    let drop = movement::move_and_slide(level, &body, &Vec2::new(0.0, -lift.delta.y()));
 
-const FERRIS_WALK: StateComplex = StateComplex {
-    frames: &[
-        Frame(0, 4, 4, 100, 1),
-        Frame(1, 5, 4, 100, 2),
-        Frame(2, 6, 4, 100, 3),
-        Frame(3, 7, 4, 100, 0),
-    ],
-    think: Think::Walk,
-    react: React::Walk,
-};
+    let hits = lift
+        .hits
+        .into_iter()
+        .filter(|c| c.side == movement::Side::Top)
+        .chain(
+            walk.hits
+                .into_iter()
+                .filter(|c| c.side == movement::Side::Left || c.side == movement::Side::Right),
+        )
+        .chain(
+            drop.hits
+                .into_iter()
+                .filter(|c| c.side == movement::Side::Bottom),
+        )
+        .collect();
 
-const FERRIS_JUMP: StateComplex = StateComplex {
-    frames: &[Frame(8, 9, 0, 100, 0)],
-    think: Think::Air,
-    react: React::Air,
-};
-
-#[derive(Clone)]
-enum Movement {
-    Up,
-    Down,
-    Left,
-    Right,
-}
-#[derive(Default)]
-pub struct InputState {
-    xaxis: Option<Movement>,
-    yaxis: Option<Movement>,
-    jump: bool,
+    movement::MoveResult {
+        delta: lift.delta + walk.delta + drop.delta,
+        contacts: [
+            walk.contacts[0],
+            walk.contacts[1],
+            drop.contacts[2],
+            lift.contacts[3],
+        ],
+        hits,
+        stuck: lift.stuck || walk.stuck || drop.stuck,
+    }
 }
 
 pub struct CharacterState {
+    pub params: CharacterParams,
     input_state: InputState,
     face_dir: Direction,
-    state: &'static StateComplex,
-    state_time_left: i32,
+    state: String,
+    /// milliseconds left of the current frame
+    state_time_left: f32,
     state_step: u32,
     pixel_coord: Vec2,
+    /// pixel_coord plus the progress within the current frame, before and after the last tick
+    prev_render_coord: Vec2,
+    render_coord: Vec2,
     speed: Vec2,
+    /// collision boxes of the current sprite frame
+    boxes: FrameBoxes,
+    /// animation clock, advanced by the ticks so that the frames and with them the boxes do
+    /// not depend on the frame rate
+    animation: AnimationPlayer,
+    /// sprite frame shown after the last tick
+    sprite_index: u32,
+    /// seconds left of the buffered jump request
+    jump_buffer_left: f32,
+    /// seconds left to jump after leaving the ground without jumping
+    coyote_left: f32,
+    /// rising from a jump, releasing the button cuts it short
+    jumping: bool,
+    /// side of the wall the character slides on
+    wall: Option<Direction>,
+    /// seconds left in which horizontal input is ignored
+    input_lock_left: f32,
+    /// contacts with the level in the last tick
+    contacts: Vec<movement::ContactEvent>,
+}
+
+/// the simulated part of a CharacterState, e.g. the start of a recording
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CharacterSnapshot {
+    state: String,
+    state_step: u32,
+    state_time_left: f32,
+    face_dir: Direction,
+    /// the sprite frame decides the boxes of the next tick
+    animation: AnimationPlayer,
+    sprite_index: u32,
+    pixel_coord: (f32, f32),
+    speed: (f32, f32),
+    jump_buffer_left: f32,
+    coyote_left: f32,
+    jumping: bool,
+    wall: Option<Direction>,
+    input_lock_left: f32,
 }
 
 impl Default for CharacterState {
     fn default() -> Self {
         CharacterState {
-            state: &FERRIS_JUMP,
+            params: CharacterParams::default(),
+            // resolved to the initial state of the definition once it is loaded
+            state: String::new(),
             input_state: InputState::default(),
             face_dir: Direction::default(),
-            state_time_left: 0,
+            state_time_left: 0.0,
             state_step: 0,
             pixel_coord: Vec2::new(0.0, 14.0 * 16.0),
+            prev_render_coord: Vec2::new(0.0, 14.0 * 16.0),
+            render_coord: Vec2::new(0.0, 14.0 * 16.0),
             speed: Vec2::zero(),
+            boxes: FrameBoxes::default(),
+            animation: AnimationPlayer::default(),
+            sprite_index: 0,
+            jump_buffer_left: 0.0,
+            coyote_left: 0.0,
+            jumping: false,
+            wall: None,
+            input_lock_left: 0.0,
+            contacts: Vec::new(),
         }
     }
 }
 
-pub fn character_input(
-    keyboard_input: Res<Input<KeyCode>>,
-    mut query: Query<(&TextureAtlasSprite, &mut CharacterState)>,
-) {
-    for (_, mut state) in query.iter_mut() {
-        let speed = if keyboard_input.pressed(KeyCode::LShift) {
-            0.1
+impl CharacterState {
+    fn set_state(&mut self, def: &CharacterDef, name: &str) {
+        self.state = name.to_string();
+        self.state_step = 0;
+        self.state_time_left = def
+            .state(name)
+            .and_then(|s| s.frames.first())
+            .map_or(0.0, |f| f.3 as f32);
+    }
+
+    /// the current state; unknown states (not yet initialized or removed by a reload of the
+    /// definition) fall back to the initial state
+    fn current<'a>(&mut self, def: &'a CharacterDef) -> &'a StateComplex {
+        if def.state(&self.state).is_none() {
+            log::warn!("unknown state '{}', using '{}'", self.state, def.initial);
+            let initial = def.initial.clone();
+            self.set_state(def, &initial);
+        }
+        let statec = def.state(&self.state).unwrap();
+        if self.state_step as usize >= statec.frames.len() {
+            self.state_step = 0;
+        }
+        statec
+    }
+
+    pub fn input(&self) -> &InputState {
+        &self.input_state
+    }
+
+    pub fn set_input(&mut self, input: InputState) {
+        self.input_state = input;
+    }
+
+    pub fn pixel_coord(&self) -> Vec2 {
+        self.pixel_coord
+    }
+
+    /// boxes of the last tick
+    pub fn boxes(&self) -> &FrameBoxes {
+        &self.boxes
+    }
+
+    pub fn snapshot(&self) -> CharacterSnapshot {
+        CharacterSnapshot {
+            state: self.state.clone(),
+            state_step: self.state_step,
+            state_time_left: self.state_time_left,
+            face_dir: self.face_dir,
+            animation: self.animation.clone(),
+            sprite_index: self.sprite_index,
+            pixel_coord: (self.pixel_coord.x(), self.pixel_coord.y()),
+            speed: (self.speed.x(), self.speed.y()),
+            jump_buffer_left: self.jump_buffer_left,
+            coyote_left: self.coyote_left,
+            jumping: self.jumping,
+            wall: self.wall,
+            input_lock_left: self.input_lock_left,
+        }
+    }
+
+    /// continues the simulation from the snapshot
+    pub fn restore(&mut self, snapshot: &CharacterSnapshot) {
+        self.state = snapshot.state.clone();
+        self.state_step = snapshot.state_step;
+        self.state_time_left = snapshot.state_time_left;
+        self.face_dir = snapshot.face_dir;
+        self.animation = snapshot.animation.clone();
+        self.sprite_index = snapshot.sprite_index;
+        self.pixel_coord = Vec2::new(snapshot.pixel_coord.0, snapshot.pixel_coord.1);
+        self.prev_render_coord = self.pixel_coord;
+        self.render_coord = self.pixel_coord;
+        self.speed = Vec2::new(snapshot.speed.0, snapshot.speed.1);
+        self.jump_buffer_left = snapshot.jump_buffer_left;
+        self.coyote_left = snapshot.coyote_left;
+        self.jumping = snapshot.jumping;
+        self.wall = snapshot.wall;
+        self.input_lock_left = snapshot.input_lock_left;
+    }
+
+    /// holds the jump button, a fresh press stays pending until a tick consumed it
+    fn set_jump(&mut self, jump: bool) {
+        self.input_state.jump_pressed |= jump && !self.input_state.jump;
+        self.input_state.jump = jump;
+    }
+
+    /// counts down the jump buffer, a fresh press of the button restarts it; returns whether
+    /// a jump is requested
+    fn update_jump_buffer(&mut self, dt: f32) -> bool {
+        if self.input_state.jump_pressed {
+            self.input_state.jump_pressed = false;
+            self.jump_buffer_left = self.params.jump_buffer;
         } else {
-            1.0
+            self.jump_buffer_left = (self.jump_buffer_left - dt).max(0.0);
+        }
+        self.jump_buffer_left > 0.0
+    }
+
+    fn start_jump(&mut self, def: &CharacterDef) {
+        self.speed.set_y(self.params.jump_speed);
+        self.set_state(def, character::JUMP);
+        self.jump_buffer_left = 0.0;
+        self.coyote_left = 0.0;
+        self.jumping = true;
+    }
+
+    fn land(&mut self, def: &CharacterDef) {
+        self.set_state(def, character::WALK);
+        self.speed.set_y(0.0);
+        self.coyote_left = 0.0;
+        self.jumping = false;
+        self.wall = None;
+        self.input_lock_left = 0.0;
+    }
+
+    /// jumps off the wall the character slides on
+    fn start_wall_jump(&mut self, def: &CharacterDef) {
+        let away = match self.wall.take() {
+            Some(Direction::West) => Direction::East,
+            _ => Direction::West,
         };
+        let speed = self.params.wall_jump_speed;
+        self.speed = Vec2::new(away.to_vec().x() * speed.x(), speed.y());
+        self.face_dir = away;
+        self.set_state(def, character::WALLJUMP);
+        self.jump_buffer_left = 0.0;
+        self.input_lock_left = self.params.wall_jump_lock;
+        self.jumping = true;
+    }
+
+    /// grabs the ladder above / below the feet while up / down is pressed and centers on it
+    fn try_climb(&mut self, def: &CharacterDef, level: &level::Level) -> bool {
+        let dir = match self.input_state.yaxis {
+            Some(Movement::Up) => Direction::North,
+            Some(Movement::Down) => Direction::South,
+            _ => return false,
+        };
+        let foot = self.pixel_coord + self.boxes.foot;
+        let ladder = match level.climbable_at(&(foot + dir.to_vec())) {
+            Some(ladder) if def.state(character::CLIMB).is_some() => ladder,
+            _ => return false,
+        };
+        self.pixel_coord
+            .set_x((ladder.left + ladder.right) / 2.0 - self.boxes.foot.x());
+        self.speed = Vec2::zero();
+        self.coyote_left = 0.0;
+        self.jumping = false;
+        self.set_state(def, character::CLIMB);
+        true
+    }
 
-        state.input_state.xaxis = None;
-        if keyboard_input.pressed(KeyCode::A) {
-            state.face_dir = Direction::West;
-            state.input_state.xaxis = Some(Movement::Left);
+    /// whether the horizontal input pushes against the wall on side
+    fn pressing_into(&self, side: Direction) -> bool {
+        match (side, &self.input_state.xaxis) {
+            (Direction::West, Some(Movement::Left)) => true,
+            (Direction::East, Some(Movement::Right)) => true,
+            _ => false,
         }
+    }
+
+    /// accelerates downwards up to max_fall_speed
+    fn fall(&mut self, dt: f32, max_fall_speed: f32) {
+        let fall_speed = self.speed.y() - self.params.gravity * dt;
+        self.speed.set_y(fall_speed.max(-max_fall_speed));
+    }
+
+    /// while rising from a jump with the button released the upwards speed is capped
+    fn cut_jump(&mut self) {
+        if self.speed.y() <= 0.0 {
+            self.jumping = false;
+        } else if self.jumping && !self.input_state.jump {
+            let cut = self.speed.y().min(self.params.jump_release_speed);
+            self.speed.set_y(cut);
+            self.jumping = false;
+        }
+    }
 
-        if keyboard_input.pressed(KeyCode::D) {
-            state.face_dir = Direction::East;
-            if state.input_state.xaxis.is_none() {
-                state.input_state.xaxis = Some(Movement::Right);
-            } else {
-                // left and right cancel out
-                state.input_state.xaxis = None;
+    /// plays the animation tag of the current state for d_ms milliseconds, states without tags
+    /// show their frame sprites
+    fn animate(&mut self, def: &CharacterDef, sheet: Option<&spritesheet::Spritesheet>, d_ms: f32) {
+        let statec = self.current(def);
+        match statec.animation {
+            Some(ref tags) => {
+                let tag = match self.face_dir {
+                    Direction::West => &tags.left,
+                    _ => &tags.right,
+                };
+                let started = self.animation.tag() != Some(tag.as_str());
+                self.animation.play(tag);
+                let sheet = match sheet {
+                    Some(sheet) => sheet,
+                    None => return,
+                };
+                match sheet.animations.get(tag) {
+                    Some(animation) if !animation.frames.is_empty() => {
+                        self.sprite_index = self.animation.advance(animation, d_ms)
+                    }
+                    _ if started => log::warn!("unknown animation tag '{}'", tag),
+                    _ => (),
+                }
+            }
+            None => {
+                self.animation.stop();
+                let frame = &statec.frames[self.state_step as usize];
+                self.sprite_index = match self.face_dir {
+                    Direction::West => frame.0,
+                    Direction::East => frame.1,
+                    _ => 0,
+                } as u32;
             }
         }
+    }
+}
 
-        state.input_state.jump = keyboard_input.pressed(KeyCode::RControl);
+pub fn character_input(
+    actions: Res<Actions>,
+    replay: Res<Replay>,
+    mut query: Query<&mut CharacterState>,
+) {
+    // a replay feeds the recorded input per tick
+    if replay.is_playing() {
+        return;
+    }
+    let left = actions.pressed(Action::MoveLeft);
+    let right = actions.pressed(Action::MoveRight);
+    let up = actions.pressed(Action::Up);
+    let down = actions.pressed(Action::Down);
+
+    for mut state in query.iter_mut() {
+        // opposite directions cancel out
+        state.input_state.xaxis = match (left, right) {
+            (true, false) => Some(Movement::Left),
+            (false, true) => Some(Movement::Right),
+            _ => None,
+        };
+        state.input_state.yaxis = match (up, down) {
+            (true, false) => Some(Movement::Up),
+            (false, true) => Some(Movement::Down),
+            _ => None,
+        };
+        state.set_jump(actions.pressed(Action::Jump));
+        state.input_state.run = actions.pressed(Action::Run);
     }
 }
 
+/// runs the fixed ticks of this frame for all characters
 pub fn character_move_state(
-    time: Res<Time>,
+    timestep: Res<FixedTimestep>,
+    mut replay: ResMut<Replay>,
     level: Res<Option<level::Level>>,
-    mut query: Query<(&mut Transform, &mut CharacterState)>,
+    character_defs: Res<Assets<CharacterDef>>,
+    spritesheet_assets: Res<Assets<spritesheet::Spritesheet>>,
+    mut contact_events: ResMut<Events<movement::ContactEvent>>,
+    mut query: Query<(
+        Entity,
+        &mut CharacterState,
+        &Handle<CharacterDef>,
+        &Handle<spritesheet::Spritesheet>,
+        &mut CharacterBoxes,
+    )>,
 ) {
     let level = match *level {
         Some(ref level) => level,
         None => return,
     };
 
-    for (mut transform, mut state) in query.iter_mut() {
-        println!("dtime: {:?}", time.delta);
-        let d_ms = (time.delta_seconds * 1000.0) as i32;
+    for (entity, mut state, def, sprite_sheet, mut world_boxes) in query.iter_mut() {
+        let def = match character_defs.get(def) {
+            Some(def) => def,
+            None => continue,
+        };
+        let sheet = spritesheet_assets.get(sprite_sheet);
+        for _ in 0..timestep.ticks() {
+            replay.before_tick(&mut state);
+            for event in state.step(def, level, sheet, entity, timestep.step) {
+                contact_events.send(event);
+            }
+            replay.after_tick(&state);
+        }
+        world_boxes.update(&state.boxes, state.render_coord);
+    }
+}
+
+/// places the sprite between the last two simulated positions and shows the simulated frame
+pub fn character_transform(
+    timestep: Res<FixedTimestep>,
+    spritesheet_assets: Res<Assets<spritesheet::Spritesheet>>,
+    mut query: Query<(
+        &mut Transform,
+        &CharacterState,
+        &mut TextureAtlasSprite,
+        &Handle<spritesheet::Spritesheet>,
+    )>,
+) {
+    for (mut transform, state, mut sprite, sprite_sheet) in query.iter_mut() {
+        sprite.index = state.sprite_index;
+        let sprite_sheet = spritesheet_assets.get(sprite_sheet);
+        let sprite_offset = sprite_sheet.map_or(Vec2::zero(), |s| s.frame_offset(sprite.index));
+        let coord = state
+            .prev_render_coord
+            .lerp(state.render_coord, timestep.alpha());
+        transform.translation = (coord + sprite_offset).extend(0.0);
+        transform.rotation =
+            sprite_sheet.map_or(Quat::identity(), |s| s.frame_rotation(sprite.index));
+    }
+}
+
+impl CharacterState {
+    /// advances the character by one tick of dt seconds, returns the contacts with the level
+    /// that were not there in the previous tick. the boxes come from the sprite frame of the
+    /// last tick, without a sheet the defaults are used
+    pub fn step(
+        &mut self,
+        def: &CharacterDef,
+        level: &level::Level,
+        sheet: Option<&spritesheet::Spritesheet>,
+        entity: Entity,
+        dt: f32,
+    ) -> Vec<movement::ContactEvent> {
+        let mut contacts = Vec::new();
+        let mut add_contact = |contact: movement::ContactEvent| {
+            if !contacts.contains(&contact) {
+                contacts.push(contact);
+            }
+        };
+        let state = self;
+        state.boxes = sheet.map_or_else(FrameBoxes::default, |s| {
+            FrameBoxes::from_spritesheet(s, state.sprite_index)
+        });
+        if state.input_lock_left <= 0.0 {
+            match state.input_state.xaxis {
+                Some(Movement::Left) => state.face_dir = Direction::West,
+                Some(Movement::Right) => state.face_dir = Direction::East,
+                _ => (),
+            }
+        }
+        let statec = state.current(def);
+        let d_ms = dt * 1000.0;
         let mut movex = 0f32;
         let mut movey = 0f32;
+        let run = if state.input_state.run {
+            state.params.run_speed
+        } else {
+            1.0
+        };
         state.state_time_left -= d_ms;
-        println!("time: {} {}", state.state_time_left, d_ms);
-        let mut intra_frame = Vec2::zero();
+        let mut intra_frame;
 
-        while state.state_time_left <= 0 {
-            state.state_step = state.state.frames[state.state_step as usize].4;
-            let Frame(sl, sr, x, time, next) = &state.state.frames[state.state_step as usize];
-            state.state_time_left += time;
+        while state.state_time_left <= 0.0 {
+            state.state_step = statec.frames[state.state_step as usize].4;
+            let Frame(_, _, x, time, _) = &statec.frames[state.state_step as usize];
+            state.state_time_left += *time as f32;
 
             movex += match state.input_state.xaxis {
                 Some(Movement::Left) => -x,
                 Some(Movement::Right) => *x,
                 _ => 0,
-            } as f32;
-            // movex += state.state_time_left += statec.frames[state.state_step as usize].3;
+            } as f32
+                * run;
         }
 
         {
-            let Frame(_, _, x, time, _) = &state.state.frames[state.state_step as usize];
-            let prog = 1.0 - state.state_time_left as f32 / *time as f32;
+            let Frame(_, _, x, time, _) = &statec.frames[state.state_step as usize];
+            let prog = 1.0 - state.state_time_left / *time as f32;
             let x = match state.input_state.xaxis {
                 Some(Movement::Left) => -x,
                 Some(Movement::Right) => *x,
                 _ => 0,
             };
-            intra_frame = Vec2::new(x as f32, 0f32) * prog;
+            intra_frame = Vec2::new(x as f32 * run, 0f32) * prog;
         }
-        match state.state.think {
+        let jump_requested = state.update_jump_buffer(dt);
+        match statec.think {
             Think::Walk => {
-                // println!("walk")
-                if state.input_state.jump {
-                    state.speed.set_y(100.0);
-                    state.state = &FERRIS_JUMP;
-                    state.state_step = 0;
-                    state.state_time_left = FERRIS_JUMP.frames[0].3;
-                    movey += state.speed.y() * time.delta_seconds;
-                    let runjump_speed = 32f32;
+                if jump_requested {
+                    state.start_jump(def);
+                    let run_jump_speed = state.params.run_jump_speed;
                     let speed = match state.input_state.xaxis {
-                        Some(Movement::Left) => -runjump_speed,
-                        Some(Movement::Right) => runjump_speed,
+                        Some(Movement::Left) => -run_jump_speed,
+                        Some(Movement::Right) => run_jump_speed,
                         _ => 0f32,
                     };
                     state.speed.set_x(speed);
+                } else if state.try_climb(def, level) {
+                    movex = 0.0;
                 } else if state.input_state.xaxis.is_none() {
-                    state.state = &FERRIS_STAND;
-                    state.state_step = 0;
-                    state.state_time_left = FERRIS_STAND.frames[0].3;
+                    state.set_state(def, character::STAND);
                 }
             }
             Think::Air => {
-                movey += state.speed.y() * time.delta_seconds;
+                // ladders can be caught on the way
+                let climbing =
+                    state.input_state.yaxis == Some(Movement::Up) && state.try_climb(def, level);
+                if !climbing {
+                    if jump_requested && state.coyote_left > 0.0 {
+                        state.start_jump(def);
+                    }
+                    state.coyote_left = (state.coyote_left - dt).max(0.0);
+                    state.cut_jump();
 
-                if state.speed.y() > -50.0 {
-                    *state.speed.y_mut() -= 5.0;
-                }
+                    let max_fall_speed = state.params.max_fall_speed;
+                    state.fall(dt, max_fall_speed);
 
-                match state.input_state.xaxis.clone() {
-                    Some(movement) => do_accel_x(&mut state.speed, &movement),
-                    None => do_friction_x(&mut state.speed),
-                }
+                    match state.input_state.xaxis.clone() {
+                        Some(movement) => do_accel_x(&mut state.speed, &movement, run, dt),
+                        None => do_friction_x(&mut state.speed, dt),
+                    }
 
-                // match state.input_state.xaxis {
-                //     Some(Movement::Left) => *state.speed.x_mut() -= 4f32,
-                //     Some(Movement::Right) => *state.speed.x_mut() += 4f32,
-                //     _ => *state.speed.x_mut() *= 0.5,
-                // }
-                movex += state.speed.x() * time.delta_seconds;
-                movey += state.speed.y() * time.delta_seconds;
+                    movex += state.speed.x() * dt;
+                    movey += state.speed.y() * dt;
+                }
             }
             Think::Stand => {
-                if state.input_state.jump {
-                    state.speed.set_y(100.0);
-                    state.state = &FERRIS_JUMP;
-                    state.state_step = 0;
-                    state.state_time_left = FERRIS_JUMP.frames[0].3;
-                    movey += state.speed.y() * time.delta_seconds;
-                } else if state.input_state.xaxis.is_some() {
-                    state.state = &FERRIS_WALK;
-                    state.state_step = 0;
-                    state.state_time_left = FERRIS_WALK.frames[0].3;
+                if jump_requested {
+                    state.start_jump(def);
+                } else if !state.try_climb(def, level) && state.input_state.xaxis.is_some() {
+                    state.set_state(def, character::WALK);
+                }
+            }
+            Think::Climb => {
+                state.speed = Vec2::zero();
+                if jump_requested {
+                    state.start_jump(def);
+                } else {
+                    let dir = match state.input_state.yaxis {
+                        Some(Movement::Up) => Direction::North.to_vec(),
+                        Some(Movement::Down) => Direction::South.to_vec(),
+                        _ => Vec2::zero(),
+                    };
+                    movey += dir.y() * state.params.climb_speed * dt;
                 }
             }
+            Think::WallSlide => {
+                let wall = state.wall.unwrap_or(state.face_dir);
+                if jump_requested && def.state(character::WALLJUMP).is_some() {
+                    state.start_wall_jump(def);
+                    movex += state.speed.x() * dt;
+                    movey += state.speed.y() * dt;
+                } else if !state.pressing_into(wall) {
+                    state.wall = None;
+                    state.set_state(def, character::JUMP);
+                } else {
+                    let wall_slide_speed = state.params.wall_slide_speed;
+                    state.fall(dt, wall_slide_speed);
+                    state.speed.set_x(0.0);
+                    // keep touching the wall so that react notices when it ends
+                    movex += wall.to_vec().x();
+                    movey += state.speed.y() * dt;
+                }
+            }
+            Think::WallJump => {
+                state.input_lock_left = (state.input_lock_left - dt).max(0.0);
+                state.cut_jump();
+                let max_fall_speed = state.params.max_fall_speed;
+                state.fall(dt, max_fall_speed);
+                if state.input_lock_left <= 0.0 {
+                    state.set_state(def, character::JUMP);
+                }
+                movex += state.speed.x() * dt;
+                movey += state.speed.y() * dt;
+            }
+        }
+
+        // think may have switched to another state
+        let statec = state.current(def);
+        let body = body_rect(state.pixel_coord, &state.boxes, &state.params);
+        let move_res = match statec.react {
+            React::Walk | React::Stand => walk_move(level, &body, movex, state.params.step_up),
+            React::Air | React::WallSlide | React::Climb => {
+                movement::move_and_slide(level, &body, &Vec2::new(movex, movey))
+            }
+        };
+        let mut new_pixel_coord = state.pixel_coord + move_res.delta;
+        let [contact_left, contact_right, contact_bottom, contact_top] = move_res.contacts;
+        if contact_left || contact_right {
+            state.speed.set_x(0.0);
+            intra_frame = Vec2::zero();
+        }
+        if contact_top && state.speed.y() > 0.0 {
+            state.speed.set_y(0.0);
         }
+        let moved_body = movement::offset_rect(&body, &move_res.delta);
+        for hit in move_res.hits.iter() {
+            let bounds = level.collision_shapes[hit.shape_index].bounds();
+            add_contact(movement::ContactEvent {
+                entity,
+                side: hit.side,
+                shape_index: hit.shape_index,
+                tile_gid: level.tile_gid_at(&movement::contact_point(
+                    &moved_body,
+                    hit.side,
+                    &bounds,
+                )),
+            });
+        }
+
+        let prev_foot = state.pixel_coord + state.boxes.foot;
+        let foot = new_pixel_coord + state.boxes.foot;
 
-        println!(
-            "move: {} {} speed {:?} {:?} {} think: {:?}",
-            movex, movey, state.speed, state.state, state.state_step, state.state.think
-        );
-        // let pixel_coord = transform.translation.truncate();
-        // let mut d = (state.velocity * 128.0 * time.delta_seconds).truncate();
-        let new_pixel_coord = state.pixel_coord + Vec2::new(movex as f32, movey);
-
-        println!("coord: {:?} {:?}", state.pixel_coord, new_pixel_coord);
-        let probe_pos = new_pixel_coord + Vec2::new(8.0, -14.0);
-
-        let mut on_ground = false;
-
-        for shape in level.collision_shapes.iter() {
-            let level::CollisionShape::Rect(r1) = shape;
-            if probe_pos.x() >= r1.left
-                && probe_pos.x() <= r1.right
-                && probe_pos.y() >= r1.bottom
-                && probe_pos.y() <= r1.top
-            {
-                on_ground = true;
-                break;
+        // grounded states follow the surface up and down, in the air only surfaces crossed
+        // by the feet during this step count as landing
+        let probe_range = match statec.react {
+            React::Walk | React::Stand => Some((
+                foot.y() - state.params.snap_down,
+                foot.y() + state.params.step_up,
+            )),
+            React::Air | React::WallSlide if state.speed.y() <= 0.0 => {
+                Some((foot.y(), foot.y() - move_res.delta.y().min(0.0)))
+            }
+            React::Air | React::WallSlide => None,
+            React::Climb if move_res.delta.y() < 0.0 => {
+                Some((foot.y(), foot.y() - move_res.delta.y()))
+            }
+            React::Climb => None,
+        };
+        let ground = probe_range.and_then(|(bottom, top)| {
+            let probe = math::Rect {
+                left: foot.x(),
+                right: foot.x(),
+                top,
+                bottom,
+            };
+            movement::find_ground(level.indexed_shapes_in_rect(&probe), foot.x(), bottom, top)
+        });
+        let max_slope_angle = state.params.max_slope_angle;
+        let ground =
+            ground.filter(|(_, normal, _)| movement::slope_angle(normal) <= max_slope_angle);
+
+        // ladder tops carry everyone but the climbers
+        let ladder_top = match statec.react {
+            React::Climb => None,
+            _ => probe_range.and_then(|(bottom, top)| level.ladder_top(foot.x(), bottom, top)),
+        };
+
+        // the hull resting on an edge the feet do not reach counts as ground as well
+        let on_ground = ground.is_some() || ladder_top.is_some() || contact_bottom;
+        if let Some((height, _, shape_index)) = ground {
+            new_pixel_coord.set_y(height - state.boxes.foot.y());
+            // the ground under the feet is a bottom contact as well, walking onto another
+            // tile changes it
+            add_contact(movement::ContactEvent {
+                entity,
+                side: movement::Side::Bottom,
+                shape_index,
+                tile_gid: level.tile_gid_at(&Vec2::new(foot.x(), height - 0.5)),
+            });
+        }
+        if let Some(height) = ladder_top {
+            if ground.map_or(true, |(ground_height, _, _)| height > ground_height) {
+                new_pixel_coord.set_y(height - state.boxes.foot.y());
             }
         }
 
-        println!("intra: {:?}", intra_frame);
-        transform.translation = (new_pixel_coord + intra_frame).extend(0.0);
         state.pixel_coord = new_pixel_coord;
-        match state.state.react {
-            React::Walk => {
+        state.prev_render_coord = state.render_coord;
+        state.render_coord = new_pixel_coord + intra_frame;
+        match statec.react {
+            React::Walk | React::Stand => {
                 // println!("react walk");
                 if !on_ground {
-                    state.state = &FERRIS_JUMP;
-                    state.state_step = 0;
-                    state.state_time_left = FERRIS_WALK.frames[0].3;
+                    state.set_state(def, character::JUMP);
+                    state.coyote_left = state.params.coyote_time;
                 }
             }
             React::Air => {
                 // println!("react air: {}", on_ground);
+                let wall = match (contact_left, contact_right) {
+                    (true, false) => Some(Direction::West),
+                    (false, true) => Some(Direction::East),
+                    _ => None,
+                };
                 if on_ground {
-                    state.state = &FERRIS_WALK;
-                    state.state_step = 0;
-                    state.state_time_left = FERRIS_WALK.frames[0].3;
-                    state.speed.set_y(0.0);
+                    state.land(def);
+                } else if let Some(wall) = wall {
+                    // grab walls when falling and pushing into them
+                    if state.speed.y() <= 0.0
+                        && state.input_lock_left <= 0.0
+                        && state.pressing_into(wall)
+                        && def.state(character::WALLSLIDE).is_some()
+                    {
+                        state.wall = Some(wall);
+                        state.set_state(def, character::WALLSLIDE);
+                    }
                 }
             }
-            React::Stand => {}
+            React::Climb => match level.climbable_at(&prev_foot) {
+                // step off at the top onto the platform
+                Some(ladder) if foot.y() > ladder.top => {
+                    state.pixel_coord.set_y(ladder.top - state.boxes.foot.y());
+                    state.render_coord = state.pixel_coord;
+                    state.set_state(def, character::STAND);
+                }
+                _ if on_ground => state.set_state(def, character::STAND),
+                Some(ladder) if foot.y() >= ladder.bottom => (),
+                _ => state.set_state(def, character::JUMP),
+            },
+            React::WallSlide => {
+                let touching = match state.wall {
+                    Some(Direction::West) => contact_left,
+                    Some(Direction::East) => contact_right,
+                    _ => false,
+                };
+                if on_ground {
+                    state.land(def);
+                } else if !touching {
+                    state.wall = None;
+                    state.set_state(def, character::JUMP);
+                }
+            }
+        }
+
+        state.animate(def, sheet, d_ms);
+
+        // standing or sliding touches the same tiles every tick, only send what is new
+        let new_contacts = contacts
+            .iter()
+            .filter(|contact| !state.contacts.contains(contact))
+            .cloned()
+            .collect();
+        state.contacts = contacts;
+        new_contacts
+    }
+}
+
+/// after a level reload characters may end up inside of new geometry: move them to the nearest
+/// free spot
+pub fn relocate_embedded_characters(
+    level: ChangedRes<Option<level::Level>>,
+    mut query: Query<&mut CharacterState>,
+) {
+    let level = match *level {
+        Some(ref level) => level,
+        None => return,
+    };
+
+    for mut state in query.iter_mut() {
+        let body = body_rect(state.pixel_coord, &state.boxes, &state.params);
+        match movement::find_free_spot(level, &body, 32) {
+            Some(offset) if offset.length_squared() > 0.0 => {
+                log::debug!("relocate character by {:?}", offset);
+                state.pixel_coord += offset;
+                state.prev_render_coord = state.pixel_coord;
+                state.render_coord = state.pixel_coord;
+            }
+            Some(_) => (),
+            None => log::warn!("no free spot near {:?}", state.pixel_coord),
         }
     }
 }
 
-fn do_friction_x(speed: &mut Vec2) -> () {
-    let decel = 1f32;
+fn do_friction_x(speed: &mut Vec2, dt: f32) -> () {
+    let decel = 60.0 * dt;
     if speed.x().abs() <= decel {
         speed.set_x(0f32)
     } else if speed.x() > 0.0 {
@@ -366,9 +924,9 @@ fn do_friction_x(speed: &mut Vec2) -> () {
     }
 }
 
-fn do_accel_x(speed: &mut Vec2, movement: &Movement) -> () {
-    let accel = 4.0;
-    let maxspeed = 32.0;
+fn do_accel_x(speed: &mut Vec2, movement: &Movement, run: f32, dt: f32) -> () {
+    let accel = 240.0 * dt;
+    let maxspeed = 32.0 * run;
     let x = speed.x_mut();
     match movement {
         Movement::Right => {
@@ -387,17 +945,10 @@ fn do_accel_x(speed: &mut Vec2, movement: &Movement) -> () {
     }
 }
 
-pub(crate) fn spawn(
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
-) -> () {
-    // let texture_handle = asset_server.load("gabe-idle-run.png");
-    // let texture_atlas = TextureAtlas::from_grid(texture_handle, Vec2::new(24.0, 24.0), 7, 1);
-    let desc: Handle<spritesheet::Spritesheet> = asset_server.load("ferris2.0.json");
-    let texture_handle = asset_server.load("ferris2.0.png");
-    let texture_atlas = TextureAtlas::from_grid(texture_handle, Vec2::new(16.0, 16.0), 10, 1);
-    let texture_atlas_handle = texture_atlases.add(texture_atlas);
+pub(crate) fn spawn(mut commands: Commands, asset_server: Res<AssetServer>) -> () {
+    let desc: Handle<spritesheet::Spritesheet> = asset_server.load("ferris2.0.sheet");
+    let texture_atlas_handle: Handle<TextureAtlas> = asset_server.load("ferris2.0.sheet#atlas");
+    let def: Handle<CharacterDef> = asset_server.load("ferris.character");
 
     commands
         .spawn(SpriteSheetComponents {
@@ -409,7 +960,232 @@ pub(crate) fn spawn(
             },
             ..Default::default()
         })
-        .with(Timer::from_seconds(0.1, true))
         .with(CharacterState::default())
-        .with(desc);
+        .with(CharacterBoxes::default())
+        .with(Health::default())
+        .with(desc)
+        .with(def);
+}
+
+#[test]
+fn test_jump_buffer_and_cut() {
+    let mut state = CharacterState::default();
+    state.input_state.jump = true;
+    state.input_state.jump_pressed = true;
+    // the press is remembered for jump_buffer seconds only
+    assert!(state.update_jump_buffer(0.016));
+    assert!(state.update_jump_buffer(0.05));
+    assert!(!state.update_jump_buffer(0.06));
+
+    state.speed.set_y(state.params.jump_speed);
+    state.jumping = true;
+    state.cut_jump();
+    assert_eq!(state.speed.y(), state.params.jump_speed);
+    state.input_state.jump = false;
+    state.cut_jump();
+    assert_eq!(state.speed.y(), state.params.jump_release_speed);
+    assert!(!state.jumping);
+}
+
+#[test]
+fn test_headless_simulation() {
+    let def = CharacterDef::from_bytes(&std::fs::read("assets/ferris.character").unwrap()).unwrap();
+    let level = level::Level::from_shapes(vec![level::CollisionShape::Rect(math::Rect {
+        left: -1000.0,
+        right: 1000.0,
+        top: 32.0,
+        bottom: 0.0,
+    })]);
+    let dt = FixedTimestep::default().step;
+
+    // (ticks, xaxis, jump): fall down, walk right, stop, hop
+    let script = [
+        (60, None, false),
+        (120, Some(Movement::Right), false),
+        (30, None, false),
+        (1, None, true),
+        (120, None, false),
+    ];
+    let run = || {
+        let mut state = CharacterState::default();
+        state.pixel_coord = Vec2::new(0.0, 80.0);
+        let mut ticks = 0;
+        for (n, xaxis, jump) in script.iter() {
+            for _ in 0..*n {
+                state.input_state.xaxis = xaxis.clone();
+                state.set_jump(*jump);
+                state.step(&def, &level, None, Entity::new(0), dt);
+                ticks += 1;
+                if *jump {
+                    assert_eq!(state.state, "jump");
+                }
+            }
+        }
+        assert_eq!(ticks, 331);
+        state
+    };
+
+    let state = run();
+    // standing on the floor after walking right for a second at 4 pixels per 100ms
+    assert_eq!(state.state, "stand");
+    assert_eq!(state.pixel_coord.y(), 32.0 - state.boxes.foot.y());
+    assert!(state.pixel_coord.x() > 30.0 && state.pixel_coord.x() < 50.0);
+    assert_eq!(state.speed, Vec2::zero());
+
+    // identical input gives a bit-identical trajectory
+    let mut again = run();
+    assert_eq!(
+        state.pixel_coord.x().to_bits(),
+        again.pixel_coord.x().to_bits()
+    );
+    assert_eq!(
+        state.pixel_coord.y().to_bits(),
+        again.pixel_coord.y().to_bits()
+    );
+
+    // the floor is touched every tick while standing, but only reported when it begins
+    assert_eq!(again.contacts.len(), 1);
+    for _ in 0..10 {
+        assert!(again
+            .step(&def, &level, None, Entity::new(0), dt)
+            .is_empty());
+    }
+}
+
+#[test]
+fn test_run() {
+    let def = CharacterDef::from_bytes(&std::fs::read("assets/ferris.character").unwrap()).unwrap();
+    let level = level::Level::from_shapes(vec![level::CollisionShape::Rect(math::Rect {
+        left: -1000.0,
+        right: 1000.0,
+        top: 32.0,
+        bottom: 0.0,
+    })]);
+    let dt = FixedTimestep::default().step;
+    let walk = |run| {
+        let mut state = CharacterState::default();
+        state.pixel_coord = Vec2::new(0.0, 80.0);
+        for _ in 0..60 {
+            state.step(&def, &level, None, Entity::new(0), dt);
+        }
+        state.input_state.xaxis = Some(Movement::Right);
+        state.input_state.run = run;
+        for _ in 0..120 {
+            state.step(&def, &level, None, Entity::new(0), dt);
+        }
+        state.pixel_coord.x()
+    };
+    let (walked, ran) = (walk(false), walk(true));
+    assert!(walked > 0.0);
+    assert_relative_eq!(ran, walked * 1.5, epsilon = 1e-3);
+}
+
+#[test]
+fn test_wall_slide_and_jump() {
+    let def = CharacterDef::from_bytes(&std::fs::read("assets/ferris.character").unwrap()).unwrap();
+    let rect = |left, right, top, bottom| {
+        level::CollisionShape::Rect(math::Rect {
+            left,
+            right,
+            top,
+            bottom,
+        })
+    };
+    // floor and a wall on the right
+    let level = level::Level::from_shapes(vec![
+        rect(-1000.0, 1000.0, 32.0, 0.0),
+        rect(40.0, 56.0, 400.0, 0.0),
+    ]);
+    let dt = FixedTimestep::default().step;
+    let mut state = CharacterState::default();
+    state.pixel_coord = Vec2::new(24.0, 150.0);
+    let mut run = |state: &mut CharacterState, ticks, xaxis: Option<Movement>, jump| {
+        for _ in 0..ticks {
+            state.input_state.xaxis = xaxis.clone();
+            state.set_jump(jump);
+            state.step(&def, &level, None, Entity::new(0), dt);
+        }
+    };
+
+    // pushing into the wall while falling slides down slowly
+    run(&mut state, 40, Some(Movement::Right), false);
+    assert_eq!(state.state, "wallslide");
+    assert_eq!(state.wall, Some(Direction::East));
+    assert_relative_eq!(
+        state.pixel_coord.x(),
+        40.0 - state.boxes.body.right,
+        epsilon = 1e-3
+    );
+    assert!(state.speed.y() >= -state.params.wall_slide_speed);
+    assert!(state.pixel_coord.y() > 100.0);
+
+    // jumping pushes away from the wall, holding towards it does not matter for a moment
+    run(&mut state, 1, Some(Movement::Right), true);
+    assert_eq!(state.state, "walljump");
+    assert!(state.speed.x() < 0.0);
+    run(&mut state, 10, Some(Movement::Right), true);
+    assert_eq!(state.state, "walljump");
+    assert!(state.pixel_coord.x() < 40.0 - state.boxes.body.right - 2.0);
+    assert!(state.speed.y() > 0.0);
+
+    // then the regular air control takes over
+    run(&mut state, 20, Some(Movement::Right), true);
+    assert_eq!(state.state, "jump");
+    assert_eq!(state.input_lock_left, 0.0);
+}
+
+#[test]
+fn test_climb_ladder() {
+    let def = CharacterDef::from_bytes(&std::fs::read("assets/ferris.character").unwrap()).unwrap();
+    let rect = |left, right, top, bottom| math::Rect {
+        left,
+        right,
+        top,
+        bottom,
+    };
+    // a ladder from the floor up through a gap in a platform
+    let mut level = level::Level::from_shapes(vec![
+        level::CollisionShape::Rect(rect(-1000.0, 1000.0, 32.0, 0.0)),
+        level::CollisionShape::Rect(rect(-1000.0, 16.0, 96.0, 80.0)),
+        level::CollisionShape::Rect(rect(32.0, 1000.0, 96.0, 80.0)),
+    ]);
+    level.climbable = vec![rect(16.0, 32.0, 96.0, 32.0)];
+    let dt = FixedTimestep::default().step;
+    let mut state = CharacterState::default();
+    state.pixel_coord = Vec2::new(10.0, 50.0);
+    let mut run = |state: &mut CharacterState, ticks, xaxis: Option<Movement>, yaxis| {
+        for _ in 0..ticks {
+            state.input_state.xaxis = xaxis.clone();
+            state.input_state.yaxis = yaxis.clone();
+            state.step(&def, &level, None, Entity::new(0), dt);
+        }
+    };
+    let foot_y = |state: &CharacterState| state.pixel_coord.y() + state.boxes.foot.y();
+
+    run(&mut state, 30, None, None);
+    assert_eq!(state.state, "stand");
+    assert_eq!(foot_y(&state), 32.0);
+
+    // up grabs the ladder and centers on it, there is no gravity on it
+    run(&mut state, 60, None, Some(Movement::Up));
+    assert_eq!(state.state, "climb");
+    assert_eq!(state.pixel_coord.x() + state.boxes.foot.x(), 24.0);
+    let y = foot_y(&state);
+    assert!(y > 40.0 && y < 96.0);
+    run(&mut state, 30, None, None);
+    assert_eq!(foot_y(&state), y);
+
+    // at the top the character steps off onto the platform and walks away
+    run(&mut state, 200, None, Some(Movement::Up));
+    assert_eq!(state.state, "stand");
+    assert_eq!(foot_y(&state), 96.0);
+    run(&mut state, 10, Some(Movement::Right), None);
+    assert_eq!(state.state, "walk");
+    assert_eq!(foot_y(&state), 96.0);
+
+    // down from the top of the ladder to the floor
+    run(&mut state, 1, None, None);
+    run(&mut state, 250, None, Some(Movement::Down));
+    assert_eq!(state.state, "stand");
+    assert_eq!(foot_y(&state), 32.0);
 }
@@ -0,0 +1,232 @@
+use super::{level, movement, spritesheet::Spritesheet};
+use bevy::{math, prelude::*};
+
+/// collision boxes of one sprite frame relative to the character position, taken from the
+/// spritesheet slices "body", "foot", "hitbox*" and "hurtbox*"
+#[derive(Debug, Clone)]
+pub struct FrameBoxes {
+    /// collision hull, its bottom is replaced by foot + step_up when moving
+    pub body: math::Rect<f32>,
+    /// ground sensor under the feet (pivot of the "foot" slice or the bottom center of its bounds)
+    pub foot: Vec2,
+    /// boxes dealing damage
+    pub hitboxes: Vec<math::Rect<f32>>,
+    /// boxes receiving damage
+    pub hurtboxes: Vec<math::Rect<f32>>,
+}
+
+impl Default for FrameBoxes {
+    fn default() -> Self {
+        let foot = Vec2::new(8.0, -14.0);
+        let body = math::Rect {
+            left: foot.x() - 5.0,
+            right: foot.x() + 5.0,
+            top: foot.y() + 14.0,
+            bottom: foot.y(),
+        };
+        FrameBoxes {
+            body,
+            foot,
+            hitboxes: Vec::new(),
+            hurtboxes: vec![body],
+        }
+    }
+}
+
+/// slice bounds are in sprite pixels with y pointing down, the character position is the top
+/// left corner of the sprite
+fn slice_rect(bounds: &bevy::sprite::Rect) -> math::Rect<f32> {
+    math::Rect {
+        left: bounds.min.x(),
+        right: bounds.max.x(),
+        top: -bounds.min.y(),
+        bottom: -bounds.max.y(),
+    }
+}
+
+impl FrameBoxes {
+    /// boxes of the sprite frame, missing slices keep the defaults
+    pub fn from_spritesheet(sheet: &Spritesheet, frame: u32) -> FrameBoxes {
+        let mut boxes = FrameBoxes::default();
+        let key = |name: &str| sheet.slices.get(name).and_then(|s| s.key_at(frame));
+
+        if let Some(key) = key("foot") {
+            let pivot = key.pivot.unwrap_or_else(|| {
+                Vec2::new(
+                    (key.bounds.max.x() - key.bounds.min.x()) / 2.0,
+                    key.bounds.max.y() - key.bounds.min.y(),
+                )
+            });
+            let foot = key.bounds.min + pivot;
+            boxes.foot = Vec2::new(foot.x(), -foot.y());
+        }
+        if let Some(key) = key("body") {
+            boxes.body = slice_rect(&key.bounds);
+        }
+
+        let named = |prefix: &str| -> Vec<math::Rect<f32>> {
+            let mut names: Vec<&String> = sheet
+                .slices
+                .keys()
+                .filter(|name| name.starts_with(prefix))
+                .collect();
+            names.sort();
+            names
+                .into_iter()
+                .filter_map(|name| key(name))
+                .map(|key| slice_rect(&key.bounds))
+                .collect()
+        };
+        boxes.hitboxes = named("hitbox");
+        let hurtboxes = named("hurtbox");
+        boxes.hurtboxes = if hurtboxes.is_empty() {
+            vec![math::Rect {
+                bottom: boxes.foot.y(),
+                ..boxes.body
+            }]
+        } else {
+            hurtboxes
+        };
+        boxes
+    }
+}
+
+/// hit- and hurtboxes of a character in world space, updated when it moves
+#[derive(Default, Debug)]
+pub struct CharacterBoxes {
+    pub hitboxes: Vec<math::Rect<f32>>,
+    pub hurtboxes: Vec<math::Rect<f32>>,
+}
+
+impl CharacterBoxes {
+    pub fn update(&mut self, boxes: &FrameBoxes, pixel_coord: Vec2) {
+        let place = |r: &math::Rect<f32>| movement::offset_rect(r, &pixel_coord);
+        self.hitboxes = boxes.hitboxes.iter().map(place).collect();
+        self.hurtboxes = boxes.hurtboxes.iter().map(place).collect();
+    }
+}
+
+/// a hitbox of attacker started to overlap a hurtbox of target
+#[derive(Debug, Clone, Copy)]
+pub struct HitEvent {
+    pub attacker: Entity,
+    pub target: Entity,
+}
+
+/// hit points of a character, every hit takes one
+#[derive(Debug)]
+pub struct Health {
+    pub hp: u32,
+}
+
+impl Default for Health {
+    fn default() -> Self {
+        Health { hp: 3 }
+    }
+}
+
+/// sends a HitEvent when an overlap begins, boxes overlapping for several frames hit once
+pub fn character_hit(
+    mut overlapping: Local<Vec<(Entity, Entity)>>,
+    mut hit_events: ResMut<Events<HitEvent>>,
+    query: Query<(Entity, &CharacterBoxes)>,
+) {
+    let mut now = Vec::new();
+    for (attacker, attacker_boxes) in query.iter() {
+        if attacker_boxes.hitboxes.is_empty() {
+            continue;
+        }
+        for (target, target_boxes) in query.iter() {
+            if attacker == target {
+                continue;
+            }
+            let hit = attacker_boxes.hitboxes.iter().any(|hitbox| {
+                target_boxes
+                    .hurtboxes
+                    .iter()
+                    .any(|hurtbox| level::rects_overlap(hitbox, hurtbox))
+            });
+            if hit {
+                if !overlapping.contains(&(attacker, target)) {
+                    hit_events.send(HitEvent { attacker, target });
+                }
+                now.push((attacker, target));
+            }
+        }
+    }
+    *overlapping = now;
+}
+
+pub fn take_hits(
+    mut hit_reader: Local<EventReader<HitEvent>>,
+    hit_events: Res<Events<HitEvent>>,
+    mut query: Query<(Entity, &mut Health)>,
+) {
+    let targets: Vec<Entity> = hit_reader.iter(&hit_events).map(|hit| hit.target).collect();
+    if targets.is_empty() {
+        return;
+    }
+    for (entity, mut health) in query.iter_mut() {
+        let hits = targets.iter().filter(|target| **target == entity).count() as u32;
+        if hits > 0 {
+            health.hp = health.hp.saturating_sub(hits);
+            log::info!("{:?} was hit, {} hp left", entity, health.hp);
+        }
+    }
+}
+
+#[test]
+fn test_frame_boxes_from_slices() {
+    let json = br##"{
+        "frames": [
+            { "frame": { "x": 0, "y": 0, "w": 16, "h": 16 }, "trimmed": false,
+              "spriteSourceSize": { "x": 0, "y": 0, "w": 16, "h": 16 },
+              "sourceSize": { "w": 16, "h": 16 }, "duration": 100 },
+            { "frame": { "x": 16, "y": 0, "w": 16, "h": 16 }, "trimmed": false,
+              "spriteSourceSize": { "x": 0, "y": 0, "w": 16, "h": 16 },
+              "sourceSize": { "w": 16, "h": 16 }, "duration": 100 }
+        ],
+        "meta": {
+            "app": "", "version": "", "image": "", "format": "",
+            "size": { "w": 32, "h": 16 }, "scale": "1", "frameTags": [],
+            "slices": [
+                { "name": "body", "color": "#0000ffff", "keys": [
+                    { "frame": 0, "bounds": { "x": 4, "y": 2, "w": 8, "h": 12 } } ] },
+                { "name": "foot", "color": "#0000ffff", "keys": [
+                    { "frame": 0, "bounds": { "x": 6, "y": 13, "w": 4, "h": 2 } } ] },
+                { "name": "hitbox claw", "color": "#ff0000ff", "keys": [
+                    { "frame": 1, "bounds": { "x": 12, "y": 4, "w": 4, "h": 3 } } ] }
+            ]
+        }
+    }"##;
+    let sheet =
+        Spritesheet::try_from_bytes(std::path::Path::new("t.sheet"), json.to_vec()).unwrap();
+
+    let boxes = FrameBoxes::from_spritesheet(&sheet, 0);
+    assert_eq!(boxes.foot, Vec2::new(8.0, -15.0));
+    assert_eq!(
+        (
+            boxes.body.left,
+            boxes.body.right,
+            boxes.body.top,
+            boxes.body.bottom
+        ),
+        (4.0, 12.0, -2.0, -14.0)
+    );
+    assert!(boxes.hitboxes.is_empty());
+    // without hurtbox slices the body down to the feet gets hurt
+    assert_eq!(boxes.hurtboxes.len(), 1);
+    assert_eq!(boxes.hurtboxes[0].bottom, -15.0);
+
+    let boxes = FrameBoxes::from_spritesheet(&sheet, 1);
+    assert_eq!(boxes.hitboxes.len(), 1);
+    assert_eq!(
+        (boxes.hitboxes[0].left, boxes.hitboxes[0].top),
+        (12.0, -4.0)
+    );
+
+    let mut world = CharacterBoxes::default();
+    world.update(&boxes, Vec2::new(100.0, 50.0));
+    assert_eq!(world.hitboxes[0].left, 112.0);
+    assert_eq!(world.hitboxes[0].bottom, 43.0);
+}
@@ -1,5 +1,6 @@
 use bevy::{math::Rect, prelude::*};
 
+use std::{collections::HashMap, fmt};
 use tiled::Tileset;
 
 use bevy_tiled_prototype::Map;
@@ -7,116 +8,917 @@ use bevy_tiled_prototype::Map;
 #[derive(Debug)]
 pub enum CollisionShape {
     Rect(Rect<f32>),
+    /// convex polygon, points in world space
+    Polygon(Vec<Vec2>),
+}
+
+impl CollisionShape {
+    /// axis aligned bounding box
+    pub fn bounds(&self) -> Rect<f32> {
+        match self {
+            CollisionShape::Rect(rect) => *rect,
+            CollisionShape::Polygon(points) => points.iter().fold(
+                Rect {
+                    left: f32::MAX,
+                    right: f32::MIN,
+                    top: f32::MIN,
+                    bottom: f32::MAX,
+                },
+                |r, p| Rect {
+                    left: r.left.min(p.x()),
+                    right: r.right.max(p.x()),
+                    top: r.top.max(p.y()),
+                    bottom: r.bottom.min(p.y()),
+                },
+            ),
+        }
+    }
+}
+
+const GRID_CELL_SIZE: f32 = 64.0;
+
+/// uniform grid broadphase, each cell lists the indices of the shapes whose bounds touch it
+#[derive(Default)]
+struct ShapeGrid {
+    cells: HashMap<(i32, i32), Vec<usize>>,
+    bounds: Vec<Rect<f32>>,
+}
+
+impl ShapeGrid {
+    fn new(shapes: &[CollisionShape]) -> Self {
+        let mut grid = ShapeGrid::default();
+        for (i, shape) in shapes.iter().enumerate() {
+            let bounds = shape.bounds();
+            let (x0, y0, x1, y1) = ShapeGrid::cell_range(&bounds);
+            for y in y0..=y1 {
+                for x in x0..=x1 {
+                    grid.cells.entry((x, y)).or_insert_with(Vec::new).push(i);
+                }
+            }
+            grid.bounds.push(bounds);
+        }
+        grid
+    }
+
+    fn cell_range(rect: &Rect<f32>) -> (i32, i32, i32, i32) {
+        (
+            (rect.left / GRID_CELL_SIZE).floor() as i32,
+            (rect.bottom / GRID_CELL_SIZE).floor() as i32,
+            (rect.right / GRID_CELL_SIZE).floor() as i32,
+            (rect.top / GRID_CELL_SIZE).floor() as i32,
+        )
+    }
+
+    /// sorted indices of all shapes whose bounds touch rect
+    fn query(&self, rect: &Rect<f32>) -> Vec<usize> {
+        let (x0, y0, x1, y1) = ShapeGrid::cell_range(rect);
+        let mut indices = Vec::new();
+        for y in y0..=y1 {
+            for x in x0..=x1 {
+                if let Some(cell) = self.cells.get(&(x, y)) {
+                    indices.extend(
+                        cell.iter()
+                            .filter(|i| rects_overlap(&self.bounds[**i], rect)),
+                    );
+                }
+            }
+        }
+        // shapes spanning several cells show up more than once
+        indices.sort_unstable();
+        indices.dedup();
+        indices
+    }
 }
 
 pub struct Level {
+    /// do not modify directly, the broadphase is only built on construction
     pub collision_shapes: Vec<CollisionShape>,
+    /// gids of the solid tiles by cell, in tiles from the origin with y pointing up. merged
+    /// shapes span tiles with different gids, so the gid of a contact is looked up by position
+    tile_cells: HashMap<(i32, i32), u32>,
+    grid: ShapeGrid,
+    /// ladders: tiles with the property `climbable = true`, merged into columns. they are not
+    /// solid, only the top of a column can be stood on
+    pub climbable: Vec<Rect<f32>>,
 }
 
-impl Level {
-    pub fn new(map: &tiled::Map) -> Self {
-        let mut collision_shapes = Vec::new();
-        if map.tilesets.len() != 1 {
-            panic!("only support one tileset");
+#[derive(Debug)]
+pub enum LevelError {
+    MissingLayerData {
+        layer: String,
+        x: i32,
+        y: i32,
+    },
+    UnknownGid {
+        layer: String,
+        x: i32,
+        y: i32,
+        gid: u32,
+    },
+    /// the collision code only separates convex polygons
+    ConcavePolygon {
+        layer: String,
+        x: i32,
+        y: i32,
+        gid: u32,
+    },
+}
+
+impl fmt::Display for LevelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LevelError::MissingLayerData { layer, x, y } => {
+                write!(f, "layer '{}': no tile data at ({}, {})", layer, x, y)
+            }
+            LevelError::UnknownGid { layer, x, y, gid } => write!(
+                f,
+                "layer '{}': gid {} at ({}, {}) is not part of any tileset",
+                layer, gid, x, y
+            ),
+            LevelError::ConcavePolygon { layer, x, y, gid } => write!(
+                f,
+                "layer '{}': tile {} at ({}, {}) has a concave collision polygon, split it \
+                 into convex ones",
+                layer, gid, x, y
+            ),
         }
-        let tileset: &Tileset = &map.tilesets[0];
+    }
+}
+
+impl std::error::Error for LevelError {}
+
+/// tileset with its tiles indexed by local tile id
+struct TilesetTiles<'a> {
+    tileset: &'a Tileset,
+    tiles: HashMap<u32, &'a tiled::Tile>,
+}
 
-        println!("first gid: {}", tileset.first_gid);
-        let mut tile_map = std::collections::HashMap::new();
+impl<'a> TilesetTiles<'a> {
+    fn new(tileset: &'a Tileset) -> Self {
+        let mut tiles = HashMap::new();
         for tile in tileset.tiles.iter() {
-            println!("tilemap: {}", tile.id);
-            tile_map.insert(tile.id, tile);
+            tiles.insert(tile.id, tile);
+        }
+        TilesetTiles { tileset, tiles }
+    }
+
+    fn contains(&self, gid: u32) -> bool {
+        gid >= self.tileset.first_gid
+            && match self.tileset.tilecount {
+                Some(tilecount) => gid < self.tileset.first_gid + tilecount,
+                None => true,
+            }
+    }
+}
+
+/// find the tileset a gid belongs to, i.e. the one with the highest first_gid not above gid
+fn resolve_gid<'a, 'b>(tilesets: &'b [TilesetTiles<'a>], gid: u32) -> Option<&'b TilesetTiles<'a>> {
+    tilesets
+        .iter()
+        .filter(|t| t.tileset.first_gid <= gid)
+        .max_by_key(|t| t.tileset.first_gid)
+        .filter(|t| t.contains(gid))
+}
+
+/// greedy meshing of full tiles, whatever their gid: runs are grown along rows first, then
+/// stacked downwards while the rows below have the same run. Returns (x, y, width, height) in
+/// tile coordinates.
+fn merge_solid_tiles(solid: &[Vec<bool>]) -> Vec<(usize, usize, usize, usize)> {
+    let mut used: Vec<Vec<bool>> = solid.iter().map(|row| vec![false; row.len()]).collect();
+    let free = |used: &Vec<Vec<bool>>, x: usize, y: usize| {
+        solid[y].get(x).cloned().unwrap_or(false) && !used[y][x]
+    };
+
+    let mut rects = Vec::new();
+    for (y, row) in solid.iter().enumerate() {
+        for x in 0..row.len() {
+            if !free(&used, x, y) {
+                continue;
+            }
+            let mut w = 1;
+            while free(&used, x + w, y) {
+                w += 1;
+            }
+            let mut h = 1;
+            while y + h < solid.len() && (x..x + w).all(|x| free(&used, x, y + h)) {
+                h += 1;
+            }
+            for used_row in used[y..y + h].iter_mut() {
+                for cell in used_row[x..x + w].iter_mut() {
+                    *cell = true;
+                }
+            }
+            rects.push((x, y, w, h));
+        }
+    }
+    rects
+}
+
+/// whether the polygon is convex, in either winding order
+fn is_convex(points: &[Vec2]) -> bool {
+    let mut sign = 0.0;
+    for (i, p0) in points.iter().enumerate() {
+        let p1 = points[(i + 1) % points.len()];
+        let p2 = points[(i + 2) % points.len()];
+        let (e0, e1) = (p1 - *p0, p2 - p1);
+        let cross = e0.x() * e1.y() - e0.y() * e1.x();
+        if cross.abs() < f32::EPSILON {
+            continue;
+        }
+        if cross * sign < 0.0 {
+            return false;
+        }
+        sign = cross;
+    }
+    true
+}
+
+/// stacks the climbable tiles of each column into one rect
+fn merge_climbable(mut rects: Vec<Rect<f32>>) -> Vec<Rect<f32>> {
+    rects.sort_by(|a, b| {
+        (a.left, a.bottom)
+            .partial_cmp(&(b.left, b.bottom))
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    let mut merged: Vec<Rect<f32>> = Vec::new();
+    for rect in rects {
+        match merged.last_mut() {
+            Some(last)
+                if last.left == rect.left
+                    && last.right == rect.right
+                    && last.top == rect.bottom =>
+            {
+                last.top = rect.top
+            }
+            _ => merged.push(rect),
         }
-        // tileset.tiles
+    }
+    merged
+}
+
+pub fn rects_overlap(a: &Rect<f32>, b: &Rect<f32>) -> bool {
+    a.left <= b.right && a.right >= b.left && a.bottom <= b.top && a.top >= b.bottom
+}
+
+pub struct LevelOptions {
+    /// merge full tile rects into larger rectangles (custom tile shapes are kept as they are)
+    pub merge_tiles: bool,
+}
+
+impl Default for LevelOptions {
+    fn default() -> Self {
+        LevelOptions { merge_tiles: true }
+    }
+}
+
+/// everything needed to resolve tiles while building the level
+struct MapContext<'a> {
+    map: &'a tiled::Map,
+    tilesets: Vec<TilesetTiles<'a>>,
+    options: &'a LevelOptions,
+}
+
+impl Level {
+    pub fn new(map: &tiled::Map) -> Result<Self, LevelError> {
+        Level::with_options(map, &LevelOptions::default())
+    }
+
+    pub fn with_options(map: &tiled::Map, options: &LevelOptions) -> Result<Self, LevelError> {
+        let ctx = MapContext {
+            map,
+            tilesets: map.tilesets.iter().map(TilesetTiles::new).collect(),
+            options,
+        };
+        let mut level = Level::from_shapes(Vec::new());
         for layer in map.layers.iter() {
             if !layer.visible {
                 continue;
             }
-            for y in 0..map.height {
-                let mut line = String::new();
-                let y2 = map.height - y - 1;
-                for x in 0..map.width {
-                    let map_tile = match &layer.tiles {
-                        tiled::LayerData::Finite(tiles) => &tiles[y as usize][x as usize],
-                        _ => panic!("Infinte maps not supported"),
-                    };
-                    let rect = Rect {
-                        left: (x * 16) as f32,
-                        right: (x * 16 + 16) as f32,
-                        top: (y2 * 16 + 16) as f32,
-                        bottom: (y2 * 16) as f32,
-                    };
-                    // println!( "rect: {:?}", rect);
-                    if map_tile.gid != 0 {
-                        let mut shape = None;
-                        line.push('#');
-                        if map_tile.gid < tileset.first_gid
-                            || map_tile.gid >= tileset.tilecount.unwrap()
-                        {
-                            panic!("tile gid out of range: {}", map_tile.gid);
+            match &layer.tiles {
+                tiled::LayerData::Finite(tiles) => {
+                    level.add_chunk(&ctx, &layer.name, (0, 0), (map.width, map.height), tiles)?
+                }
+                tiled::LayerData::Infinite(chunks) => {
+                    // sorted for a stable shape order independent of the HashMap
+                    let mut keys: Vec<_> = chunks.keys().collect();
+                    keys.sort();
+                    for key in keys {
+                        let chunk = &chunks[key];
+                        level.add_chunk(
+                            &ctx,
+                            &layer.name,
+                            (chunk.x, chunk.y),
+                            (chunk.width, chunk.height),
+                            &chunk.tiles,
+                        )?;
+                    }
+                }
+            }
+        }
+        level.grid = ShapeGrid::new(&level.collision_shapes);
+        level.climbable = merge_climbable(std::mem::take(&mut level.climbable));
+        Ok(level)
+    }
+
+    pub fn from_shapes(collision_shapes: Vec<CollisionShape>) -> Self {
+        let grid = ShapeGrid::new(&collision_shapes);
+        Level {
+            tile_cells: HashMap::new(),
+            collision_shapes,
+            grid,
+            climbable: Vec::new(),
+        }
+    }
+
+    /// gid of the solid tile at p, 0 if there is none
+    pub fn tile_gid_at(&self, p: &Vec2) -> u32 {
+        let cell = ((p.x() / 16.0).floor() as i32, (p.y() / 16.0).floor() as i32);
+        self.tile_cells.get(&cell).cloned().unwrap_or(0)
+    }
+
+    /// build collision shapes for a block of tiles whose top left tile is at origin (in tile
+    /// coordinates, may be negative for infinite maps)
+    fn add_chunk(
+        &mut self,
+        ctx: &MapContext,
+        layer_name: &str,
+        origin: (i32, i32),
+        (width, height): (u32, u32),
+        tiles: &[Vec<tiled::LayerTile>],
+    ) -> Result<(), LevelError> {
+        let map = ctx.map;
+        let collision_shapes = &mut self.collision_shapes;
+        let tile_cells = &mut self.tile_cells;
+        let climbable = &mut self.climbable;
+        // full tiles that are left for the merge pass
+        let mut solid = vec![vec![false; width as usize]; height as usize];
+        for ty in 0..height {
+            let mut line = String::new();
+            let y = origin.1 + ty as i32;
+            // tiled y points down, flip it around the (nominal) map height
+            let y2 = map.height as i32 - y - 1;
+            for tx in 0..width {
+                let x = origin.0 + tx as i32;
+                let map_tile = tiles
+                    .get(ty as usize)
+                    .and_then(|row| row.get(tx as usize))
+                    .ok_or_else(|| LevelError::MissingLayerData {
+                        layer: layer_name.into(),
+                        x,
+                        y,
+                    })?;
+                let rect = Rect {
+                    left: (x * 16) as f32,
+                    right: (x * 16 + 16) as f32,
+                    top: (y2 * 16 + 16) as f32,
+                    bottom: (y2 * 16) as f32,
+                };
+                // println!( "rect: {:?}", rect);
+                if map_tile.gid != 0 {
+                    let mut shapes = Vec::new();
+                    line.push('#');
+                    let tileset = resolve_gid(&ctx.tilesets, map_tile.gid).ok_or_else(|| {
+                        LevelError::UnknownGid {
+                            layer: layer_name.into(),
+                            x,
+                            y,
+                            gid: map_tile.gid,
                         }
-                        let idx = map_tile.gid - tileset.first_gid;
-                        println!("lookup: {}", idx);
-                        let tile = tile_map.get(&idx).unwrap();
-                        match &tile.objectgroup {
-                            Some(objectgroup) => {
-                                for obj in objectgroup.objects.iter() {
-                                    match &obj.shape {
-                                        tiled::ObjectShape::Rect { width, height } => {
-                                            println!(
-                                                "rect: {} {} {} {}",
-                                                obj.x, obj.y, width, height
-                                            );
-                                            let bottom =
-                                                (y2 * 16) as f32 + (16f32 - (obj.y + height));
-                                            let top = bottom + height;
-                                            let left = (x * 16) as f32 + obj.x;
-                                            let right = left + width;
-                                            // TODO: transform tiled Object shape (top,left -> bottom,left...)
-                                            shape = Some(CollisionShape::Rect(Rect {
-                                                left,
-                                                right,
-                                                top,
-                                                bottom,
-                                            }));
-                                        }
-                                        tiled::ObjectShape::Polygon { points } => {
-                                            println!("polygon: {}", points.len());
+                    })?;
+                    let idx = map_tile.gid - tileset.tileset.first_gid;
+                    // tiled only writes entries for tiles with properties or shapes, the
+                    // others are plain full tiles
+                    let tile = tileset.tiles.get(&idx);
+                    if let Some(tiled::PropertyValue::BoolValue(true)) =
+                        tile.and_then(|tile| tile.properties.get("climbable"))
+                    {
+                        climbable.push(rect);
+                        continue;
+                    }
+                    tile_cells.insert((x, y2), map_tile.gid);
+                    match tile.and_then(|tile| tile.objectgroup.as_ref()) {
+                        Some(objectgroup) => {
+                            for obj in objectgroup.objects.iter() {
+                                match &obj.shape {
+                                    tiled::ObjectShape::Rect { width, height } => {
+                                        let bottom = (y2 * 16) as f32 + (16f32 - (obj.y + height));
+                                        let top = bottom + height;
+                                        let left = (x * 16) as f32 + obj.x;
+                                        let right = left + width;
+                                        // TODO: transform tiled Object shape (top,left -> bottom,left...)
+                                        shapes.push(CollisionShape::Rect(Rect {
+                                            left,
+                                            right,
+                                            top,
+                                            bottom,
+                                        }));
+                                    }
+                                    tiled::ObjectShape::Polygon { points } => {
+                                        // tiled points are relative to the object origin with y pointing down
+                                        let points = points
+                                            .iter()
+                                            .map(|(px, py)| {
+                                                Vec2::new(
+                                                    (x * 16) as f32 + obj.x + px,
+                                                    (y2 * 16) as f32 + (16f32 - (obj.y + py)),
+                                                )
+                                            })
+                                            .collect::<Vec<_>>();
+                                        if !is_convex(&points) {
+                                            return Err(LevelError::ConcavePolygon {
+                                                layer: layer_name.into(),
+                                                x,
+                                                y,
+                                                gid: map_tile.gid,
+                                            });
                                         }
-                                        _ => (),
+                                        shapes.push(CollisionShape::Polygon(points));
                                     }
+                                    _ => (),
                                 }
                             }
-                            _ => (),
                         }
-                        if let Some(shape) = shape {
-                            collision_shapes.push(shape);
-                        } else {
-                            collision_shapes.push(CollisionShape::Rect(rect));
+                        _ => (),
+                    }
+                    // without usable objects the tile is solid
+                    if shapes.is_empty() {
+                        shapes.push(CollisionShape::Rect(rect));
+                    }
+                    for shape in shapes {
+                        match shape {
+                            CollisionShape::Rect(r)
+                                if ctx.options.merge_tiles
+                                    && r.left == rect.left
+                                    && r.right == rect.right
+                                    && r.top == rect.top
+                                    && r.bottom == rect.bottom =>
+                            {
+                                solid[ty as usize][tx as usize] = true
+                            }
+                            shape => collision_shapes.push(shape),
                         }
-                    } else {
-                        line.push(' ')
                     }
-
-                    // println!("map tile: {:?}", map_tile);
+                } else {
+                    line.push(' ')
                 }
-                println!("{}", line)
+
+                // println!("map tile: {:?}", map_tile);
             }
+            log::debug!("{}", line)
+        }
+
+        for (x, y, w, h) in merge_solid_tiles(&solid) {
+            let left = (origin.0 + x as i32) * 16;
+            // top row of the merged rect, flipped like the single tiles above
+            let y2 = map.height as i32 - (origin.1 + y as i32) - 1;
+            collision_shapes.push(CollisionShape::Rect(Rect {
+                left: left as f32,
+                right: (left + w as i32 * 16) as f32,
+                top: (y2 * 16 + 16) as f32,
+                bottom: ((y2 - h as i32 + 1) * 16) as f32,
+            }));
+        }
+
+        Ok(())
+    }
+
+    /// shapes whose bounding box touches rect
+    pub fn shapes_in_rect<'a>(
+        &'a self,
+        rect: &Rect<f32>,
+    ) -> impl Iterator<Item = &'a CollisionShape> + 'a {
+        self.indexed_shapes_in_rect(rect).map(|(_, shape)| shape)
+    }
+
+    /// like shapes_in_rect, together with the index into collision_shapes
+    pub fn indexed_shapes_in_rect<'a>(
+        &'a self,
+        rect: &Rect<f32>,
+    ) -> impl Iterator<Item = (usize, &'a CollisionShape)> + 'a {
+        self.grid
+            .query(rect)
+            .into_iter()
+            .map(move |i| (i, &self.collision_shapes[i]))
+    }
+
+    /// the ladder p is on, edges included
+    pub fn climbable_at(&self, p: &Vec2) -> Option<&Rect<f32>> {
+        self.climbable
+            .iter()
+            .find(|r| r.left <= p.x() && p.x() <= r.right && r.bottom <= p.y() && p.y() <= r.top)
+    }
+
+    /// highest ladder top below x between bottom and top, ladder tops can be stood on
+    pub fn ladder_top(&self, x: f32, bottom: f32, top: f32) -> Option<f32> {
+        self.climbable
+            .iter()
+            .filter(|r| r.left <= x && x <= r.right && bottom <= r.top && r.top <= top)
+            .map(|r| r.top)
+            .fold(None, |best: Option<f32>, y| {
+                Some(best.map_or(y, |b| b.max(y)))
+            })
+    }
+
+    /// shapes whose bounding box contains p
+    pub fn shapes_at_point<'a>(
+        &'a self,
+        p: &Vec2,
+    ) -> impl Iterator<Item = &'a CollisionShape> + 'a {
+        self.shapes_in_rect(&Rect {
+            left: p.x(),
+            right: p.x(),
+            top: p.y(),
+            bottom: p.y(),
+        })
+    }
+}
+
+#[test]
+fn test_infinite_map_chunks() {
+    let tmx = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.4" orientation="orthogonal" renderorder="right-down" width="4" height="4" tilewidth="16" tileheight="16" infinite="1">
+ <tileset firstgid="1" name="test" tilewidth="16" tileheight="16" tilecount="1" columns="1">
+  <tile id="0"/>
+ </tileset>
+ <layer id="1" name="ground" width="4" height="4">
+  <data encoding="csv">
+   <chunk x="-2" y="0" width="2" height="2">
+1,0,
+0,1
+</chunk>
+  </data>
+ </layer>
+</map>"#;
+    let map = tiled::parse(tmx.as_bytes()).unwrap();
+    let level = Level::new(&map).unwrap();
+    assert_eq!(level.collision_shapes.len(), 2);
+
+    // tile (-2, 0): left of the origin, in the top row of the nominal map height
+    let b = level.collision_shapes[0].bounds();
+    assert_relative_eq!(b.left, -32.0);
+    assert_relative_eq!(b.right, -16.0);
+    assert_relative_eq!(b.bottom, 48.0);
+    assert_relative_eq!(b.top, 64.0);
+    // tile (-1, 1)
+    let b = level.collision_shapes[1].bounds();
+    assert_relative_eq!(b.left, -16.0);
+    assert_relative_eq!(b.bottom, 32.0);
 
-            // match &layer.tiles {
-            //     tiled::LayerData::Finite(tiles) => {
-            //         println!("size: {}", tiles.len());
+    let near = Rect {
+        left: -20.0,
+        right: -18.0,
+        top: 52.0,
+        bottom: 50.0,
+    };
+    assert_eq!(level.shapes_in_rect(&near).count(), 2);
+    let far = Rect {
+        left: 100.0,
+        right: 102.0,
+        top: 102.0,
+        bottom: 100.0,
+    };
+    assert_eq!(level.shapes_in_rect(&far).count(), 0);
+}
+
+#[test]
+fn test_untitled_map() {
+    // two tilesets without any tile entries
+    let map = tiled::parse_file(std::path::Path::new("assets/untitled.tmx")).unwrap();
+    let level = Level::with_options(&map, &LevelOptions { merge_tiles: false }).unwrap();
+    let tiles = match &map.layers[0].tiles {
+        tiled::LayerData::Finite(tiles) => tiles,
+        _ => unreachable!(),
+    };
+    let count = tiles.iter().flatten().filter(|tile| tile.gid != 0).count();
+    assert_eq!(level.collision_shapes.len(), count);
+
+    // gid 65 is the first tile of the second tileset, bottom left corner of the map
+    let bottom_left = level
+        .collision_shapes
+        .iter()
+        .map(|shape| shape.bounds())
+        .find(|b| b.left == 0.0 && b.bottom == 0.0)
+        .unwrap();
+    assert_eq!((bottom_left.right, bottom_left.top), (16.0, 16.0));
+}
+
+#[test]
+fn test_tiles_without_entries() {
+    // terrain and plain tiles mixed, only some of them have tile entries
+    let map = tiled::parse_file(std::path::Path::new("assets/ortho-map.tmx")).unwrap();
+    let level = Level::with_options(&map, &LevelOptions { merge_tiles: false }).unwrap();
+    assert!(!level.collision_shapes.is_empty());
+    assert!(level
+        .collision_shapes
+        .iter()
+        .all(|shape| matches!(shape, CollisionShape::Rect(_))));
+}
+
+#[test]
+fn test_climbable_tiles() {
+    let tmx = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.4" orientation="orthogonal" renderorder="right-down" width="3" height="4" tilewidth="16" tileheight="16" infinite="0">
+ <tileset firstgid="1" name="test" tilewidth="16" tileheight="16" tilecount="2" columns="2">
+  <tile id="0"/>
+  <tile id="1">
+   <properties>
+    <property name="climbable" type="bool" value="true"/>
+   </properties>
+  </tile>
+ </tileset>
+ <layer id="1" name="ground" width="3" height="4">
+  <data encoding="csv">
+1,2,1,
+0,2,0,
+0,2,0,
+1,1,1
+</data>
+ </layer>
+</map>"#;
+    let map = tiled::parse(tmx.as_bytes()).unwrap();
+    let level = Level::new(&map).unwrap();
+
+    // the ladder is not solid and its tiles form one column
+    assert_eq!(level.collision_shapes.len(), 3);
+    assert_eq!(level.climbable.len(), 1);
+    let ladder = level.climbable[0];
+    assert_eq!(
+        (ladder.left, ladder.right, ladder.bottom, ladder.top),
+        (16.0, 32.0, 16.0, 64.0)
+    );
+
+    assert!(level.climbable_at(&Vec2::new(24.0, 16.0)).is_some());
+    assert!(level.climbable_at(&Vec2::new(8.0, 30.0)).is_none());
+    assert_eq!(level.ladder_top(24.0, 60.0, 70.0), Some(64.0));
+    assert_eq!(level.ladder_top(24.0, 20.0, 40.0), None);
+}
 
-            //         for span in tiles {
-            //             println!("size x: {}", span.len());
-            //         }
-            //     }
-            //     _ => panic!("Infinte maps not supported"),
-            // }
+#[test]
+fn test_merge_solid_tiles() {
+    let pattern = [
+        "###.##", //
+        "###.#.", //
+        ".##++.", //
+        "......", //
+        "#.#.##", //
+    ];
+    // '#' and '+' are different tiles, both full
+    let solid: Vec<Vec<bool>> = pattern
+        .iter()
+        .map(|row| row.chars().map(|c| c != '.').collect())
+        .collect();
+
+    let rects = merge_solid_tiles(&solid);
+    // every full tile is covered exactly once and nothing else is covered
+    let mut covered = vec![vec![0; 6]; 5];
+    for (x, y, w, h) in rects.iter() {
+        for row in covered[*y..*y + *h].iter_mut() {
+            for cell in row[*x..*x + *w].iter_mut() {
+                *cell += 1;
+            }
+        }
+    }
+    for (solid_row, covered_row) in solid.iter().zip(covered.iter()) {
+        for (solid, count) in solid_row.iter().zip(covered_row.iter()) {
+            assert_eq!(*count, if *solid { 1 } else { 0 });
+        }
+    }
+    assert_eq!(rects[0], (0, 0, 3, 2));
+    // no seam between different tiles
+    assert_eq!(rects[3], (1, 2, 3, 1));
+    assert_eq!(rects.len(), 7);
+}
+
+#[test]
+fn test_merge_level_area() {
+    let tmx = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.4" orientation="orthogonal" renderorder="right-down" width="4" height="3" tilewidth="16" tileheight="16" infinite="0">
+ <tileset firstgid="1" name="test" tilewidth="16" tileheight="16" tilecount="3" columns="3">
+  <tile id="0"/>
+  <tile id="1">
+   <objectgroup draworder="index" id="2">
+    <object id="1" x="0" y="0">
+     <polygon points="0,0 16,16 0,16"/>
+    </object>
+   </objectgroup>
+  </tile>
+ </tileset>
+ <layer id="1" name="ground" width="4" height="3">
+  <data encoding="csv">
+3,1,1,0,
+1,1,2,0,
+0,1,1,1
+</data>
+ </layer>
+</map>"#;
+    let map = tiled::parse(tmx.as_bytes()).unwrap();
+    let area = |level: &Level| -> f32 {
+        level
+            .collision_shapes
+            .iter()
+            .map(|shape| match shape {
+                CollisionShape::Rect(r) => (r.right - r.left) * (r.top - r.bottom),
+                CollisionShape::Polygon(points) => {
+                    // shoelace formula
+                    let mut a = 0.0;
+                    for (i, p0) in points.iter().enumerate() {
+                        let p1 = points[(i + 1) % points.len()];
+                        a += p0.x() * p1.y() - p1.x() * p0.y();
+                    }
+                    (a / 2.0).abs()
+                }
+            })
+            .sum()
+    };
+
+    let single = Level::with_options(&map, &LevelOptions { merge_tiles: false }).unwrap();
+    let merged = Level::new(&map).unwrap();
+    assert_eq!(single.collision_shapes.len(), 9);
+    assert_eq!(merged.collision_shapes.len(), 4);
+    assert_relative_eq!(area(&single), area(&merged));
+
+    // every tile center is covered by the same number of shapes
+    for y in 0..3 {
+        for x in 0..4 {
+            let p = Vec2::new((x * 16 + 8) as f32, (y * 16 + 8) as f32);
+            assert_eq!(
+                single.shapes_at_point(&p).count(),
+                merged.shapes_at_point(&p).count()
+            );
+        }
+    }
+
+    // the merged rects cover different tiles, their gids are kept per cell
+    assert_eq!(merged.tile_gid_at(&Vec2::new(8.0, 40.0)), 3);
+    assert_eq!(merged.tile_gid_at(&Vec2::new(24.0, 40.0)), 1);
+    assert_eq!(merged.tile_gid_at(&Vec2::new(40.0, 24.0)), 2);
+    assert_eq!(merged.tile_gid_at(&Vec2::new(8.0, 8.0)), 0);
+}
+
+#[test]
+fn test_multi_object_tile() {
+    // a slope with a lip along its low end
+    let tmx = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.4" orientation="orthogonal" renderorder="right-down" width="1" height="1" tilewidth="16" tileheight="16" infinite="0">
+ <tileset firstgid="1" name="test" tilewidth="16" tileheight="16" tilecount="1" columns="1">
+  <tile id="0">
+   <objectgroup draworder="index" id="2">
+    <object id="1" x="0" y="0">
+     <polygon points="16,0 16,16 0,16"/>
+    </object>
+    <object id="2" x="0" y="12" width="4" height="4"/>
+   </objectgroup>
+  </tile>
+ </tileset>
+ <layer id="1" name="ground" width="1" height="1">
+  <data encoding="csv">
+1
+</data>
+ </layer>
+</map>"#;
+    let map = tiled::parse(tmx.as_bytes()).unwrap();
+    let level = Level::new(&map).unwrap();
+    assert_eq!(level.collision_shapes.len(), 2);
+    assert!(matches!(
+        level.collision_shapes[0],
+        CollisionShape::Polygon(_)
+    ));
+    match &level.collision_shapes[1] {
+        CollisionShape::Rect(r) => {
+            assert_eq!((r.left, r.right, r.bottom, r.top), (0.0, 4.0, 0.0, 4.0))
         }
+        shape => panic!("expected the lip, got {:?}", shape),
+    }
+}
 
-        Level { collision_shapes }
+#[test]
+fn test_concave_tile() {
+    // a step drawn as one polygon
+    let tmx = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.4" orientation="orthogonal" renderorder="right-down" width="1" height="1" tilewidth="16" tileheight="16" infinite="0">
+ <tileset firstgid="1" name="test" tilewidth="16" tileheight="16" tilecount="1" columns="1">
+  <tile id="0">
+   <objectgroup draworder="index" id="2">
+    <object id="1" x="0" y="0">
+     <polygon points="8,0 16,0 16,16 0,16 0,8 8,8"/>
+    </object>
+   </objectgroup>
+  </tile>
+ </tileset>
+ <layer id="1" name="ground" width="1" height="1">
+  <data encoding="csv">
+1
+</data>
+ </layer>
+</map>"#;
+    let map = tiled::parse(tmx.as_bytes()).unwrap();
+    match Level::new(&map) {
+        Err(LevelError::ConcavePolygon { gid, x, y, .. }) => assert_eq!((gid, x, y), (1, 0, 0)),
+        other => panic!("expected ConcavePolygon, got {:?}", other.map(|_| ())),
     }
+
+    assert!(is_convex(&[
+        Vec2::new(0.0, 0.0),
+        Vec2::new(16.0, 0.0),
+        Vec2::new(16.0, 16.0)
+    ]));
+    // collinear points do not count as a turn
+    assert!(is_convex(&[
+        Vec2::new(0.0, 0.0),
+        Vec2::new(8.0, 0.0),
+        Vec2::new(16.0, 0.0),
+        Vec2::new(0.0, 16.0)
+    ]));
+}
+
+#[test]
+fn test_shapes_in_rect() {
+    let level = Level::from_shapes(vec![
+        // spans several grid cells, including negative ones
+        CollisionShape::Rect(Rect {
+            left: -100.0,
+            right: 100.0,
+            top: 16.0,
+            bottom: 0.0,
+        }),
+        CollisionShape::Polygon(vec![
+            Vec2::new(200.0, 0.0),
+            Vec2::new(216.0, 16.0),
+            Vec2::new(216.0, 0.0),
+        ]),
+    ]);
+
+    let wide = Rect {
+        left: -80.0,
+        right: 210.0,
+        top: 8.0,
+        bottom: 4.0,
+    };
+    assert_eq!(level.shapes_in_rect(&wide).count(), 2);
+    assert_eq!(level.shapes_at_point(&Vec2::new(-90.0, 10.0)).count(), 1);
+    assert_eq!(level.shapes_at_point(&Vec2::new(208.0, 4.0)).count(), 1);
+    assert_eq!(level.shapes_at_point(&Vec2::new(150.0, 4.0)).count(), 0);
+    assert_eq!(level.shapes_at_point(&Vec2::new(-90.0, 20.0)).count(), 0);
+}
+
+/// compare the grid broadphase against a linear scan over all shapes, run with
+/// `cargo test --release bench_shapes_in_rect -- --ignored --nocapture`
+#[test]
+#[ignore]
+fn bench_shapes_in_rect() {
+    let mut shapes = Vec::new();
+    for y in 0..256 {
+        for x in 0..256 {
+            if (x * 7 + y * 13) % 3 == 0 {
+                shapes.push(CollisionShape::Rect(Rect {
+                    left: (x * 16) as f32,
+                    right: (x * 16 + 16) as f32,
+                    top: (y * 16 + 16) as f32,
+                    bottom: (y * 16) as f32,
+                }));
+            }
+        }
+    }
+    let level = Level::from_shapes(shapes);
+    let queries: Vec<_> = (0..10000)
+        .map(|i| {
+            let x = ((i * 7919) % 4096) as f32;
+            let y = ((i * 104_729) % 4096) as f32;
+            Rect {
+                left: x,
+                right: x + 12.0,
+                top: y + 16.0,
+                bottom: y,
+            }
+        })
+        .collect();
+
+    let start = std::time::Instant::now();
+    let mut linear_hits = 0;
+    for q in queries.iter() {
+        linear_hits += level
+            .collision_shapes
+            .iter()
+            .filter(|shape| rects_overlap(&shape.bounds(), q))
+            .count();
+    }
+    let linear = start.elapsed();
+
+    let start = std::time::Instant::now();
+    let mut grid_hits = 0;
+    for q in queries.iter() {
+        grid_hits += level.shapes_in_rect(q).count();
+    }
+    let grid = start.elapsed();
+
+    println!(
+        "{} shapes, {} queries: linear {:?}, grid {:?}",
+        level.collision_shapes.len(),
+        queries.len(),
+        linear,
+        grid
+    );
+    assert_eq!(linear_hits, grid_hits);
 }
 
 #[derive(Default)]
@@ -133,9 +935,14 @@ pub fn process_loaded_tile_maps2(
     for event in state.map_event_reader.iter(&map_events) {
         match event {
             AssetEvent::Created { handle } => {
-                let map = maps.get_mut(handle).unwrap();
-
-                *level = Some(Level::new(&map.map));
+                // keep the previous level if the new map cannot be used
+                match maps.get_mut(handle) {
+                    Some(map) => match Level::new(&map.map) {
+                        Ok(new_level) => *level = Some(new_level),
+                        Err(err) => log::warn!("failed to build level from {:?}: {}", handle, err),
+                    },
+                    None => log::warn!("map {:?} is gone, keeping the previous level", handle),
+                }
                 // match &layer.tiles {
                 //     tiled::LayerData::Finite(tiles) => {
                 //         println!("size: {}", tiles.len());
@@ -147,15 +954,24 @@ pub fn process_loaded_tile_maps2(
                 //     _ => panic!("Infinte maps not supported"),
                 // }
 
-                println!("created: {:?}", handle);
+                log::debug!("created: {:?}", handle);
             }
             AssetEvent::Modified { handle } => {
-                // println!("modified: {:?}", handle);
+                log::debug!("modified: {:?}", handle);
+                // map may already be gone if it was modified and removed in the same update
+                if let Some(map) = maps.get_mut(handle) {
+                    match Level::new(&map.map) {
+                        Ok(new_level) => *level = Some(new_level),
+                        Err(err) => {
+                            log::warn!("failed to rebuild level from {:?}: {}", handle, err)
+                        }
+                    }
+                }
             }
             AssetEvent::Removed { handle } => {
-                // if mesh was modified and removed in the same update, ignore the modification
-                // events are ordered so future modification events are ok
-                // println!("removed: {:?}", handle);
+                // events are ordered so a later Created / Modified event brings the level back
+                log::debug!("removed: {:?}", handle);
+                *level = None;
             }
         }
     }
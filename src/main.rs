@@ -2,10 +2,17 @@ use bevy::{prelude::*, render::camera::Camera};
 // use bevy_tiled_prototype::level;
 use bevy_tiled_prototype::TiledMapCenter;
 
+mod actions;
+mod animation;
+mod ase;
+mod character;
 mod ferris;
+mod hitbox;
 mod level;
 mod movement;
+mod replay;
 mod spritesheet;
+mod timestep;
 
 #[macro_use]
 extern crate approx;
@@ -18,27 +25,36 @@ fn main() {
         .add_plugins(DefaultPlugins)
         .add_plugin(bevy_tiled_prototype::TiledMapPlugin)
         .add_plugin(spritesheet::SpritesheetPlugin)
+        .add_plugin(character::CharacterPlugin)
+        .add_plugin(actions::ActionPlugin)
+        .add_plugin(timestep::TimestepPlugin)
+        .add_resource(replay::Replay::from_args(
+            std::env::args(),
+            "map1.tmx",
+            timestep::TICK_RATE,
+        ))
         .add_startup_system(setup.system())
         .add_system(level::process_loaded_tile_maps2.system())
         .init_resource::<Option<level::Level>>()
-        .add_system(ferris::animate_character_system.system())
+        .add_event::<movement::ContactEvent>()
+        .add_event::<hitbox::HitEvent>()
         .add_system(ferris::character_input.system())
         .add_system(ferris::character_move_state.system())
+        .add_system(ferris::relocate_embedded_characters.system())
+        .add_system(ferris::character_transform.system())
         // .add_system(ferris::character_move_state.system())
-        // .add_system(ferris::character_hit.system())
+        .add_system(hitbox::character_hit.system())
+        .add_system(hitbox::take_hits.system())
         // .add_system(ferris::character_intersect.system())
         .add_system(camera_movement.system())
+        .add_system_to_stage(stage::POST_UPDATE, replay::save_recording.system())
         .run();
 }
 
-fn setup(
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
-) {
+fn setup(mut commands: Commands, asset_server: Res<AssetServer>, replay: Res<replay::Replay>) {
     commands
         .spawn(bevy_tiled_prototype::TiledMapComponents {
-            map_asset: asset_server.load("map1.tmx"),
+            map_asset: asset_server.load(replay.recording.map.as_str()),
             center: TiledMapCenter(false),
             //origin: Transform::from_scale(Vec3::new(8.0, 8.0, 1.0)),
             origin: Transform {
@@ -53,7 +69,7 @@ fn setup(
             ..Default::default()
         });
     asset_server.watch_for_changes().unwrap();
-    ferris::spawn(commands, asset_server, texture_atlases);
+    ferris::spawn(commands, asset_server);
 }
 
 fn animate_sprite_system(
@@ -20,6 +20,10 @@ pub(crate) fn intersect_dist(shape: &level::CollisionShape, rect: &math::Rect<f3
             //     && rect.top >= shape.bottom
             //     && rect.bottom <= shape.top
         }
+        level::CollisionShape::Polygon(points) => match polygon_rect_mtv(points, rect) {
+            Some(mtv) => Vec2::new(mtv.x().abs(), mtv.y().abs()),
+            None => Vec2::zero(),
+        },
     }
 }
 
@@ -75,9 +79,194 @@ pub fn intersect_dist2(shape: &level::CollisionShape, rect: &math::Rect<f32>) ->
 
             Vec2::new(x, y)
         }
+        // for polygons the minimal translation vector is the only meaningful push direction
+        level::CollisionShape::Polygon(points) => {
+            polygon_rect_mtv(points, rect).unwrap_or_else(Vec2::zero)
+        }
+    }
+}
+
+fn rect_points(rect: &Rect<f32>) -> [Vec2; 4] {
+    [
+        Vec2::new(rect.left, rect.bottom),
+        Vec2::new(rect.right, rect.bottom),
+        Vec2::new(rect.right, rect.top),
+        Vec2::new(rect.left, rect.top),
+    ]
+}
+
+fn project(points: &[Vec2], axis: Vec2) -> (f32, f32) {
+    points.iter().fold((f32::MAX, f32::MIN), |(min, max), p| {
+        let d = p.dot(axis);
+        (min.min(d), max.max(d))
+    })
+}
+
+/// separating axes for a convex polygon vs. an axis aligned rect: the polygon edge normals plus x and y
+fn sat_axes(points: &[Vec2]) -> Vec<Vec2> {
+    let mut axes = vec![Vec2::new(1.0, 0.0), Vec2::new(0.0, 1.0)];
+    for (i, p0) in points.iter().enumerate() {
+        let p1 = points[(i + 1) % points.len()];
+        let edge = p1 - *p0;
+        if edge.length_squared() < f32::EPSILON {
+            continue;
+        }
+        axes.push(Vec2::new(-edge.y(), edge.x()).normalize());
+    }
+    axes
+}
+
+/// minimal translation vector that pushes rect out of the (convex) polygon, None if they do not overlap
+pub fn polygon_rect_mtv(points: &[Vec2], rect: &Rect<f32>) -> Option<Vec2> {
+    let corners = rect_points(rect);
+    let mut mtv: Option<(Vec2, f32)> = None;
+    for axis in sat_axes(points) {
+        let (pmin, pmax) = project(points, axis);
+        let (rmin, rmax) = project(&corners, axis);
+        if range_non_overlap(pmin, pmax, rmin, rmax) {
+            return None;
+        }
+        let (push, depth) = if pmax - rmin < rmax - pmin {
+            (axis, pmax - rmin)
+        } else {
+            (-axis, rmax - pmin)
+        };
+        match mtv {
+            Some((_, min_depth)) if min_depth <= depth => (),
+            _ => mtv = Some((push, depth)),
+        }
+    }
+    mtv.map(|(push, depth)| push * depth)
+}
+
+/// true if rect and shape overlap with a non-zero area (touching does not count)
+pub fn overlaps(shape: &level::CollisionShape, rect: &Rect<f32>) -> bool {
+    match shape {
+        level::CollisionShape::Rect(r1) => {
+            !range_non_overlap(r1.left, r1.right, rect.left, rect.right)
+                && !range_non_overlap(r1.bottom, r1.top, rect.bottom, rect.top)
+        }
+        level::CollisionShape::Polygon(points) => polygon_rect_mtv(points, rect).is_some(),
+    }
+}
+
+/// smallest offset (up to max_dist in each direction) that moves rect to a spot where it does
+/// not overlap any of the shapes
+pub fn find_free_spot(level: &level::Level, rect: &Rect<f32>, max_dist: i32) -> Option<Vec2> {
+    let d = max_dist as f32;
+    let search = Rect {
+        left: rect.left - d,
+        right: rect.right + d,
+        top: rect.top + d,
+        bottom: rect.bottom - d,
+    };
+    let shapes: Vec<_> = level.shapes_in_rect(&search).collect();
+
+    let mut offsets = Vec::new();
+    for y in -max_dist..=max_dist {
+        for x in -max_dist..=max_dist {
+            offsets.push(Vec2::new(x as f32, y as f32));
+        }
+    }
+    // prefer short distances, on ties prefer moving up
+    offsets.sort_by(|a, b| {
+        a.length_squared()
+            .partial_cmp(&b.length_squared())
+            .unwrap()
+            .then(b.y().partial_cmp(&a.y()).unwrap())
+    });
+
+    offsets.into_iter().find(|offset| {
+        let moved = Rect {
+            left: rect.left + offset.x(),
+            right: rect.right + offset.x(),
+            top: rect.top + offset.y(),
+            bottom: rect.bottom + offset.y(),
+        };
+        !shapes.iter().any(|shape| overlaps(*shape, &moved))
+    })
+}
+
+pub fn point_in_shape(shape: &level::CollisionShape, p: &Vec2) -> bool {
+    match shape {
+        level::CollisionShape::Rect(r1) => {
+            p.x() >= r1.left && p.x() <= r1.right && p.y() >= r1.bottom && p.y() <= r1.top
+        }
+        level::CollisionShape::Polygon(points) => {
+            // inside if p is on the same side of all edges (independent of winding)
+            let mut pos = false;
+            let mut neg = false;
+            for (i, p0) in points.iter().enumerate() {
+                let p1 = points[(i + 1) % points.len()];
+                let e = p1 - *p0;
+                let v = *p - *p0;
+                let cross = e.x() * v.y() - e.y() * v.x();
+                pos |= cross > 0.0;
+                neg |= cross < 0.0;
+            }
+            !(pos && neg)
+        }
+    }
+}
+
+/// height and upward facing normal of the top surface of shape at x
+pub fn ground_height(shape: &level::CollisionShape, x: f32) -> Option<(f32, Vec2)> {
+    match shape {
+        level::CollisionShape::Rect(r1) => {
+            if x >= r1.left && x <= r1.right {
+                Some((r1.top, Vec2::new(0.0, 1.0)))
+            } else {
+                None
+            }
+        }
+        level::CollisionShape::Polygon(points) => {
+            let mut ground: Option<(f32, Vec2)> = None;
+            for (i, p0) in points.iter().enumerate() {
+                let p1 = points[(i + 1) % points.len()];
+                let (l, r) = if p0.x() < p1.x() {
+                    (*p0, p1)
+                } else {
+                    (p1, *p0)
+                };
+                // vertical edges never form a top surface
+                if r.x() - l.x() < f32::EPSILON || x < l.x() || x > r.x() {
+                    continue;
+                }
+                let e = r - l;
+                let y = l.y() + e.y() * (x - l.x()) / e.x();
+                match ground {
+                    Some((height, _)) if height >= y => (),
+                    _ => ground = Some((y, Vec2::new(-e.y(), e.x()).normalize())),
+                }
+            }
+            ground
+        }
     }
 }
 
+/// angle between a surface normal and straight up, in radians
+pub fn slope_angle(normal: &Vec2) -> f32 {
+    normal.y().max(-1.0).min(1.0).acos()
+}
+
+/// highest surface at x between bottom and top (inclusive), with the index of its shape
+pub fn find_ground<'a>(
+    shapes: impl Iterator<Item = (usize, &'a level::CollisionShape)>,
+    x: f32,
+    bottom: f32,
+    top: f32,
+) -> Option<(f32, Vec2, usize)> {
+    shapes
+        .filter_map(|(i, shape)| {
+            ground_height(shape, x).map(|(height, normal)| (height, normal, i))
+        })
+        .filter(|(height, _, _)| *height >= bottom && *height <= top)
+        .fold(None, |ground, (height, normal, i)| match ground {
+            Some((h, _, _)) if h >= height => ground,
+            _ => Some((height, normal, i)),
+        })
+}
+
 #[derive(Debug, Clone)]
 pub enum MoveRes {
     Complete(Vec2),
@@ -86,8 +275,13 @@ pub enum MoveRes {
 }
 
 pub fn try_move(s1: &level::CollisionShape, r2: &Rect<f32>, d_target: &Vec2) -> MoveRes {
-    let level::CollisionShape::Rect(r1) = s1;
+    match s1 {
+        level::CollisionShape::Rect(r1) => try_move_rect(r1, r2, d_target),
+        level::CollisionShape::Polygon(points) => try_move_polygon(points, r2, d_target),
+    }
+}
 
+fn try_move_rect(r1: &Rect<f32>, r2: &Rect<f32>, d_target: &Vec2) -> MoveRes {
     let x_pos = d_target.x() > 0.0;
     let x_neg = d_target.x() < 0.0;
 
@@ -159,18 +353,260 @@ pub fn try_move(s1: &level::CollisionShape, r2: &Rect<f32>, d_target: &Vec2) ->
 
     // println!("d: {} {} {}", dx, dy, d);
 
+    // only the axis that closes last is blocking (both when hitting a corner exactly)
+    let x_hit = xfree_start && !xfree_end && dx >= dy;
+    let y_hit = yfree_start && !yfree_end && dy >= dx;
+
+    MoveRes::Collision(
+        *d_target * d,
+        d,
+        [
+            x_neg && x_hit,
+            x_pos && x_hit,
+            y_neg && y_hit,
+            y_pos && y_hit,
+        ],
+    )
+}
+
+/// swept separating axis test of a moving rect against a static convex polygon
+fn try_move_polygon(points: &[Vec2], r2: &Rect<f32>, d_target: &Vec2) -> MoveRes {
+    let corners = rect_points(r2);
+    let mut t_enter = 0f32;
+    let mut t_exit = 1f32;
+    let mut normal = None;
+
+    for axis in sat_axes(points) {
+        let (pmin, pmax) = project(points, axis);
+        let (rmin, rmax) = project(&corners, axis);
+        let v = d_target.dot(axis);
+
+        if rmax <= pmin {
+            // rect is on the negative side of the polygon
+            if v <= 0.0 {
+                return MoveRes::Complete(*d_target);
+            }
+            let enter = (pmin - rmax) / v;
+            if normal.is_none() || enter > t_enter {
+                t_enter = enter;
+                normal = Some(-axis);
+            }
+            t_exit = t_exit.min((pmax - rmin) / v);
+        } else if rmin >= pmax {
+            // rect is on the positive side of the polygon
+            if v >= 0.0 {
+                return MoveRes::Complete(*d_target);
+            }
+            let enter = (pmax - rmin) / v;
+            if normal.is_none() || enter > t_enter {
+                t_enter = enter;
+                normal = Some(axis);
+            }
+            t_exit = t_exit.min((pmin - rmax) / v);
+        } else if v > 0.0 {
+            t_exit = t_exit.min((pmax - rmin) / v);
+        } else if v < 0.0 {
+            t_exit = t_exit.min((pmin - rmax) / v);
+        }
+
+        if t_enter > 1.0 || t_enter > t_exit {
+            return MoveRes::Complete(*d_target);
+        }
+    }
+
+    let normal = match normal {
+        Some(normal) => normal,
+        None => return MoveRes::Stuck, // overlapping on all axes
+    };
+
+    let mut d = t_enter;
+    if d < f32::EPSILON * 100.0 {
+        d = 0.0;
+    }
+
+    let eps = 1e-4;
     MoveRes::Collision(
         *d_target * d,
         d,
         [
-            x_neg && !xfree_end,
-            x_pos && !xfree_end,
-            y_neg && !yfree_end,
-            y_pos && !yfree_end,
+            d_target.x() < 0.0 && normal.x() > eps,
+            d_target.x() > 0.0 && normal.x() < -eps,
+            d_target.y() < 0.0 && normal.y() > eps,
+            d_target.y() > 0.0 && normal.y() < -eps,
         ],
     )
 }
 
+/// side of a moving rect
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Side {
+    Left,
+    Right,
+    Bottom,
+    Top,
+}
+
+impl Side {
+    /// in the order of the side flags in MoveRes::Collision and MoveResult::contacts
+    pub fn all() -> &'static [Side; 4] {
+        &[Side::Left, Side::Right, Side::Bottom, Side::Top]
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Contact {
+    pub side: Side,
+    pub shape_index: usize,
+}
+
+/// sent when a character starts touching level geometry or the touched tile changes
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContactEvent {
+    pub entity: Entity,
+    pub side: Side,
+    pub shape_index: usize,
+    /// gid of the tile at the contact point, 0 if none
+    pub tile_gid: u32,
+}
+
+/// point just outside the given side of rect, within the bounds of the touched shape
+pub fn contact_point(rect: &Rect<f32>, side: Side, bounds: &Rect<f32>) -> Vec2 {
+    let center = Vec2::new(
+        (rect.left + rect.right) / 2.0,
+        (rect.bottom + rect.top) / 2.0,
+    );
+    let p = match side {
+        Side::Left => Vec2::new(rect.left - 0.5, center.y()),
+        Side::Right => Vec2::new(rect.right + 0.5, center.y()),
+        Side::Bottom => Vec2::new(center.x(), rect.bottom - 0.5),
+        Side::Top => Vec2::new(center.x(), rect.top + 0.5),
+    };
+    // half a pixel inside, so that it does not end up in the neighbouring tile
+    Vec2::new(
+        p.x().max(bounds.left + 0.5).min(bounds.right - 0.5),
+        p.y().max(bounds.bottom + 0.5).min(bounds.top - 0.5),
+    )
+}
+
+/// try_move against all level shapes touched by the swept rect, the earliest collision wins
+pub fn try_move_level(level: &level::Level, r2: &Rect<f32>, d_target: &Vec2) -> MoveRes {
+    try_move_level_contacts(level, r2, d_target).0
+}
+
+/// try_move_level, also returning the shapes that block the move
+fn try_move_level_contacts(
+    level: &level::Level,
+    r2: &Rect<f32>,
+    d_target: &Vec2,
+) -> (MoveRes, Vec<Contact>) {
+    let swept = Rect {
+        left: r2.left + d_target.x().min(0.0),
+        right: r2.right + d_target.x().max(0.0),
+        top: r2.top + d_target.y().max(0.0),
+        bottom: r2.bottom + d_target.y().min(0.0),
+    };
+
+    let mut res = MoveRes::Complete(*d_target);
+    let mut contacts = Vec::new();
+    for (shape_index, shape) in level.indexed_shapes_in_rect(&swept) {
+        let (d, t, sides) = match try_move(shape, r2, d_target) {
+            MoveRes::Stuck => return (MoveRes::Stuck, Vec::new()),
+            MoveRes::Complete(_) => continue,
+            MoveRes::Collision(d, t, sides) => (d, t, sides),
+        };
+        let shape_contacts = Side::all()
+            .iter()
+            .zip(sides.iter())
+            .filter(|(_, hit)| **hit)
+            .map(|(side, _)| Contact {
+                side: *side,
+                shape_index,
+            });
+        res = match res {
+            MoveRes::Collision(d_res, t_res, mut res_sides) if t_res <= t => {
+                // simultaneous contacts with several shapes block several sides
+                if t_res == t {
+                    for (res_side, side) in res_sides.iter_mut().zip(sides.iter()) {
+                        *res_side |= *side;
+                    }
+                    contacts.extend(shape_contacts);
+                }
+                MoveRes::Collision(d_res, t_res, res_sides)
+            }
+            _ => {
+                contacts = shape_contacts.collect();
+                MoveRes::Collision(d, t, sides)
+            }
+        };
+    }
+    (res, contacts)
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MoveResult {
+    /// movement that was actually possible
+    pub delta: Vec2,
+    /// sides of the rect that touched level geometry: left, right, bottom, top
+    pub contacts: [bool; 4],
+    /// the individual shapes that were touched
+    pub hits: Vec<Contact>,
+    pub stuck: bool,
+}
+
+pub fn offset_rect(rect: &Rect<f32>, d: &Vec2) -> Rect<f32> {
+    Rect {
+        left: rect.left + d.x(),
+        right: rect.right + d.x(),
+        top: rect.top + d.y(),
+        bottom: rect.bottom + d.y(),
+    }
+}
+
+/// move rect by delta through the level: stop at the earliest contact, drop the blocked part of
+/// the remaining movement and continue with the rest (sliding along walls and floors)
+pub fn move_and_slide(level: &level::Level, rect: &Rect<f32>, delta: &Vec2) -> MoveResult {
+    let mut res = MoveResult::default();
+    let mut rect = *rect;
+    let mut remaining = *delta;
+
+    // each iteration blocks at least one axis, so two would do; the rest is for float noise
+    for _ in 0..4 {
+        if remaining.length_squared() < f32::EPSILON {
+            break;
+        }
+        let (move_res, hits) = try_move_level_contacts(level, &rect, &remaining);
+        res.hits.extend(hits);
+        match move_res {
+            MoveRes::Complete(d) => {
+                res.delta += d;
+                break;
+            }
+            MoveRes::Stuck => {
+                res.stuck = true;
+                break;
+            }
+            MoveRes::Collision(d, t, sides) => {
+                res.delta += d;
+                rect = offset_rect(&rect, &d);
+                for (contact, side) in res.contacts.iter_mut().zip(sides.iter()) {
+                    *contact |= *side;
+                }
+                if !sides.iter().any(|side| *side) {
+                    break;
+                }
+                remaining *= 1.0 - t;
+                if sides[0] || sides[1] {
+                    remaining.set_x(0.0);
+                }
+                if sides[2] || sides[3] {
+                    remaining.set_y(0.0);
+                }
+            }
+        }
+    }
+    res
+}
+
 #[test]
 fn test_move() {
     let r1 = Rect {
@@ -190,3 +626,193 @@ fn test_move() {
     let d = try_move(&level::CollisionShape::Rect(r1), &r2, &Vec2::new(-1.5, 0.5));
     println!("d: {:?}", d);
 }
+
+#[test]
+fn test_move_polygon() {
+    // 45 degree slope rising to the right, like tile 7 in map1.tmx
+    let slope = level::CollisionShape::Polygon(vec![
+        Vec2::new(0.0, 0.0),
+        Vec2::new(16.0, 16.0),
+        Vec2::new(16.0, 0.0),
+    ]);
+
+    // falling straight down onto the slope: stops where the corner touches the surface
+    let r2 = Rect {
+        left: 10.0,
+        right: 12.0,
+        top: 22.0,
+        bottom: 20.0,
+    };
+    match try_move(&slope, &r2, &Vec2::new(0.0, -20.0)) {
+        MoveRes::Collision(d, t, sides) => {
+            assert_relative_eq!(d.y(), -8.0, epsilon = 1e-4);
+            assert_relative_eq!(t, 0.4, epsilon = 1e-4);
+            assert!(sides[2]);
+        }
+        res => panic!("unexpected: {:?}", res),
+    }
+
+    // moving away from the slope never collides
+    match try_move(&slope, &r2, &Vec2::new(-5.0, 5.0)) {
+        MoveRes::Complete(_) => (),
+        res => panic!("unexpected: {:?}", res),
+    }
+
+    // embedded in the slope
+    let r3 = Rect {
+        left: 10.0,
+        right: 12.0,
+        top: 9.0,
+        bottom: 7.0,
+    };
+    assert!(matches!(
+        try_move(&slope, &r3, &Vec2::new(1.0, 0.0)),
+        MoveRes::Stuck
+    ));
+    // shortest way out is along the slope normal
+    let mtv = intersect_dist2(&slope, &r3);
+    assert_relative_eq!(mtv.x(), -2.5, epsilon = 1e-4);
+    assert_relative_eq!(mtv.y(), 2.5, epsilon = 1e-4);
+
+    assert!(point_in_shape(&slope, &Vec2::new(12.0, 4.0)));
+    assert!(!point_in_shape(&slope, &Vec2::new(4.0, 12.0)));
+}
+
+#[test]
+fn test_ground_height() {
+    let block = level::CollisionShape::Rect(Rect {
+        left: 16.0,
+        right: 32.0,
+        top: 16.0,
+        bottom: 0.0,
+    });
+    let slope = level::CollisionShape::Polygon(vec![
+        Vec2::new(0.0, 0.0),
+        Vec2::new(16.0, 16.0),
+        Vec2::new(16.0, 0.0),
+    ]);
+
+    let (h, n) = ground_height(&slope, 4.0).unwrap();
+    assert_relative_eq!(h, 4.0);
+    assert_relative_eq!(slope_angle(&n), std::f32::consts::FRAC_PI_4, epsilon = 1e-4);
+    assert!(ground_height(&slope, 17.0).is_none());
+
+    let (h, n) = ground_height(&block, 20.0).unwrap();
+    assert_relative_eq!(h, 16.0);
+    assert_relative_eq!(slope_angle(&n), 0.0);
+
+    let shapes = [slope, block];
+    // walking up the slope: ground rises with x
+    let (h, _, i) = find_ground(shapes.iter().enumerate(), 12.0, 2.0, 18.0).unwrap();
+    assert_relative_eq!(h, 12.0);
+    assert_eq!(i, 0);
+    // descending: snap down to the slope below the feet
+    let (h, _, _) = find_ground(shapes.iter().enumerate(), 6.0, 0.0, 10.0).unwrap();
+    assert_relative_eq!(h, 6.0);
+    // out of the probe range
+    assert!(find_ground(shapes.iter().enumerate(), 6.0, 8.0, 10.0).is_none());
+    // top of the slope joins the flat block
+    let (h, _, _) = find_ground(shapes.iter().enumerate(), 16.0, 8.0, 20.0).unwrap();
+    assert_relative_eq!(h, 16.0);
+}
+
+#[test]
+fn test_find_free_spot() {
+    let level = level::Level::from_shapes(vec![
+        level::CollisionShape::Rect(Rect {
+            left: 0.0,
+            right: 16.0,
+            top: 16.0,
+            bottom: 0.0,
+        }),
+        level::CollisionShape::Rect(Rect {
+            left: 16.0,
+            right: 32.0,
+            top: 32.0,
+            bottom: 0.0,
+        }),
+    ]);
+    let shapes = &level.collision_shapes;
+    let rect = Rect {
+        left: 2.0,
+        right: 6.0,
+        top: 18.0,
+        bottom: 12.0,
+    };
+    assert!(overlaps(&shapes[0], &rect));
+    assert!(!overlaps(&shapes[1], &rect));
+
+    // pushed up out of the floor
+    let offset = find_free_spot(&level, &rect, 8).unwrap();
+    assert_relative_eq!(offset.x(), 0.0);
+    assert_relative_eq!(offset.y(), 4.0);
+
+    // already free
+    let offset = find_free_spot(
+        &level,
+        &Rect {
+            bottom: 16.0,
+            ..rect
+        },
+        8,
+    )
+    .unwrap();
+    assert_relative_eq!(offset.length(), 0.0);
+
+    // too deep inside the wall
+    let deep = Rect {
+        left: 20.0,
+        right: 28.0,
+        top: 12.0,
+        bottom: 4.0,
+    };
+    assert!(find_free_spot(&level, &deep, 2).is_none());
+}
+
+#[test]
+fn test_move_and_slide() {
+    let level = level::Level::from_shapes(vec![
+        // floor
+        level::CollisionShape::Rect(Rect {
+            left: 0.0,
+            right: 64.0,
+            top: 16.0,
+            bottom: 0.0,
+        }),
+        // wall
+        level::CollisionShape::Rect(Rect {
+            left: 32.0,
+            right: 48.0,
+            top: 48.0,
+            bottom: 16.0,
+        }),
+    ]);
+    let rect = Rect {
+        left: 10.0,
+        right: 14.0,
+        top: 20.0,
+        bottom: 16.0,
+    };
+
+    // pressed into the floor while walking into the wall: slides until the wall blocks
+    let res = move_and_slide(&level, &rect, &Vec2::new(30.0, -5.0));
+    assert!(!res.stuck);
+    assert_relative_eq!(res.delta.x(), 18.0, epsilon = 1e-4);
+    assert_relative_eq!(res.delta.y(), 0.0, epsilon = 1e-4);
+    assert_eq!(res.contacts, [false, true, true, false]);
+    let hits: Vec<_> = res.hits.iter().map(|c| (c.side, c.shape_index)).collect();
+    assert_eq!(hits, [(Side::Bottom, 0), (Side::Right, 1)]);
+
+    // falling diagonally onto the floor keeps the horizontal movement
+    let high = offset_rect(&rect, &Vec2::new(0.0, 4.0));
+    let res = move_and_slide(&level, &high, &Vec2::new(-8.0, -8.0));
+    assert_relative_eq!(res.delta.x(), -8.0, epsilon = 1e-4);
+    assert_relative_eq!(res.delta.y(), -4.0, epsilon = 1e-4);
+    assert_eq!(res.contacts, [false, false, true, false]);
+
+    // free movement
+    let res = move_and_slide(&level, &high, &Vec2::new(-2.0, 3.0));
+    assert_relative_eq!(res.delta.x(), -2.0);
+    assert_relative_eq!(res.delta.y(), 3.0);
+    assert_eq!(res.contacts, [false; 4]);
+}
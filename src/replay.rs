@@ -0,0 +1,329 @@
+use super::ferris::{CharacterSnapshot, CharacterState, InputState};
+use anyhow::{Context, Result};
+use bevy::{app::AppExit, prelude::*};
+use std::path::{Path, PathBuf};
+
+/// FNV-1a over the bits of the positions after each tick, identical trajectories give
+/// identical checksums
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Checksum(pub u64);
+
+impl Default for Checksum {
+    fn default() -> Self {
+        Checksum(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Checksum {
+    pub fn add(&mut self, coord: Vec2) {
+        for value in [coord.x(), coord.y()].iter() {
+            for byte in value.to_bits().to_le_bytes().iter() {
+                self.0 ^= *byte as u64;
+                self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+            }
+        }
+    }
+}
+
+/// map, start state and per-tick input of a recorded session
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Recording {
+    /// asset path of the map
+    pub map: String,
+    pub tick_rate: f32,
+    pub start: Option<CharacterSnapshot>,
+    pub inputs: Vec<InputState>,
+    /// checksum of the trajectory
+    pub checksum: u64,
+}
+
+impl Recording {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Recording> {
+        Ok(ron::de::from_bytes(bytes)?)
+    }
+
+    pub fn load(path: &Path) -> Result<Recording> {
+        std::fs::read(path)
+            .map_err(anyhow::Error::from)
+            .and_then(|bytes| Recording::from_bytes(&bytes))
+            .with_context(|| format!("{}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?;
+        std::fs::write(path, text).with_context(|| format!("{}", path.display()))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ReplayMode {
+    Off,
+    /// records the session and writes it to the path on exit
+    Record(PathBuf),
+    /// feeds the recorded input to the character instead of the keyboard
+    Play,
+}
+
+/// records or plays back the input of the character
+#[derive(Debug)]
+pub struct Replay {
+    pub mode: ReplayMode,
+    pub recording: Recording,
+    tick: usize,
+    checksum: Checksum,
+    finished: bool,
+}
+
+impl Replay {
+    pub fn new(mode: ReplayMode, recording: Recording) -> Self {
+        Replay {
+            mode,
+            recording,
+            tick: 0,
+            checksum: Checksum::default(),
+            finished: false,
+        }
+    }
+
+    /// "--record <file>" records, "--replay <file>" plays back a recording; without either
+    /// the session plays on map
+    pub fn from_args(args: impl Iterator<Item = String>, map: &str, tick_rate: f32) -> Self {
+        let args: Vec<String> = args.collect();
+        let value = |flag: &str| {
+            args.iter()
+                .position(|arg| arg == flag)
+                .and_then(|i| args.get(i + 1))
+                .map(PathBuf::from)
+        };
+        let recording = Recording {
+            map: map.to_string(),
+            tick_rate,
+            ..Default::default()
+        };
+
+        if let Some(path) = value("--replay") {
+            match Recording::load(&path) {
+                Ok(loaded) if loaded.tick_rate != tick_rate => log::warn!(
+                    "{}: recorded at {} Hz, not {} Hz",
+                    path.display(),
+                    loaded.tick_rate,
+                    tick_rate
+                ),
+                Ok(loaded) => {
+                    log::info!("replaying {} ticks of {}", loaded.inputs.len(), loaded.map);
+                    return Replay::new(ReplayMode::Play, loaded);
+                }
+                Err(err) => log::warn!("cannot replay: {:#}", err),
+            }
+        } else if let Some(path) = value("--record") {
+            log::info!("recording to {}", path.display());
+            return Replay::new(ReplayMode::Record(path), recording);
+        }
+        Replay::new(ReplayMode::Off, recording)
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.mode == ReplayMode::Play
+    }
+
+    /// whether playback reached the end of the recording
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    pub fn checksum(&self) -> Checksum {
+        self.checksum
+    }
+
+    pub fn before_tick(&mut self, state: &mut CharacterState) {
+        match self.mode {
+            ReplayMode::Off => (),
+            ReplayMode::Record(_) => {
+                if self.recording.start.is_none() {
+                    self.recording.start = Some(state.snapshot());
+                }
+                self.recording.inputs.push(state.input().clone());
+            }
+            ReplayMode::Play => {
+                if self.tick == 0 {
+                    if let Some(ref start) = self.recording.start {
+                        state.restore(start);
+                    }
+                }
+                // past the end the character stands still
+                let input = self.recording.inputs.get(self.tick).cloned();
+                state.set_input(input.unwrap_or_default());
+            }
+        }
+    }
+
+    pub fn after_tick(&mut self, state: &CharacterState) {
+        if self.mode == ReplayMode::Off || self.finished {
+            return;
+        }
+        self.checksum.add(state.pixel_coord());
+        self.tick += 1;
+        if let ReplayMode::Record(_) = self.mode {
+            self.recording.checksum = self.checksum.0;
+        } else if self.tick >= self.recording.inputs.len() {
+            self.finished = true;
+            if self.checksum.0 == self.recording.checksum {
+                log::info!("replay matches the recording ({:016x})", self.checksum.0);
+            } else {
+                log::warn!(
+                    "replay diverged from the recording: {:016x} instead of {:016x}",
+                    self.checksum.0,
+                    self.recording.checksum
+                );
+            }
+        }
+    }
+}
+
+/// writes the recording when the app exits
+pub fn save_recording(
+    mut exit_reader: Local<EventReader<AppExit>>,
+    exit_events: Res<Events<AppExit>>,
+    replay: Res<Replay>,
+) {
+    if exit_reader.iter(&exit_events).next().is_none() {
+        return;
+    }
+    if let ReplayMode::Record(ref path) = replay.mode {
+        match replay.recording.save(path) {
+            Ok(()) => log::info!(
+                "recorded {} ticks to {}",
+                replay.recording.inputs.len(),
+                path.display()
+            ),
+            Err(err) => log::warn!("cannot save the recording: {:#}", err),
+        }
+    }
+}
+
+#[cfg(test)]
+use super::{character::CharacterDef, level, spritesheet::Spritesheet, timestep::TICK_RATE};
+
+/// the frame tags of ferris, the feet move up and down with the frames
+#[cfg(test)]
+fn test_sheet() -> Spritesheet {
+    let frames: Vec<String> = (0..10)
+        .map(|i| {
+            format!(
+                r#"{{ "frame": {{ "x": {}, "y": 0, "w": 16, "h": 16 }}, "trimmed": false,
+                  "spriteSourceSize": {{ "x": 0, "y": 0, "w": 16, "h": 16 }},
+                  "sourceSize": {{ "w": 16, "h": 16 }}, "duration": 100 }}"#,
+                i * 16
+            )
+        })
+        .collect();
+    let keys: Vec<String> = (0..10)
+        .map(|i| {
+            format!(
+                r#"{{ "frame": {}, "bounds": {{ "x": 6, "y": {}, "w": 4, "h": 2 }} }}"#,
+                i,
+                12 + i % 3
+            )
+        })
+        .collect();
+    let tag = |name: &str, from: u32, to: u32| {
+        format!(
+            r#"{{ "name": "{}", "from": {}, "to": {}, "direction": "forward" }}"#,
+            name, from, to
+        )
+    };
+    let tags = vec![
+        tag("walk left", 0, 3),
+        tag("walk right", 4, 7),
+        tag("jump left", 8, 8),
+        tag("jump right", 9, 9),
+    ];
+    let json = format!(
+        r##"{{
+            "frames": [{}],
+            "meta": {{
+                "app": "", "version": "", "image": "", "format": "",
+                "size": {{ "w": 160, "h": 16 }}, "scale": "1", "frameTags": [{}],
+                "slices": [ {{ "name": "foot", "color": "#0000ffff", "keys": [{}] }} ]
+            }}
+        }}"##,
+        frames.join(", "),
+        tags.join(", "),
+        keys.join(", ")
+    );
+    Spritesheet::try_from_bytes(Path::new("ferris.sheet"), json.into_bytes()).unwrap()
+}
+
+#[test]
+fn test_record_and_replay() {
+    let def = CharacterDef::from_bytes(&std::fs::read("assets/ferris.character").unwrap()).unwrap();
+    let level = level::Level::from_shapes(vec![level::CollisionShape::Rect(bevy::math::Rect {
+        left: -1000.0,
+        right: 1000.0,
+        top: 32.0,
+        bottom: 0.0,
+    })]);
+    let sheet = test_sheet();
+    let sheet = Some(&sheet);
+    let dt = 1.0 / TICK_RATE;
+    let inputs: Vec<InputState> = ron::de::from_str(
+        "[(xaxis: None), (xaxis: Some(Right)), \
+         (xaxis: Some(Right), jump: true, jump_pressed: true), \
+         (xaxis: Some(Right), jump: true), (xaxis: Some(Left))]",
+    )
+    .unwrap();
+
+    let mut recorder = Replay::new(
+        ReplayMode::Record(PathBuf::from("test.replay")),
+        Recording::default(),
+    );
+    // land and walk first, the recording starts in the middle of the walk animation
+    let mut state = CharacterState::default();
+    state.set_input(inputs[1].clone());
+    for _ in 0..400 {
+        state.step(&def, &level, sheet, Entity::new(0), dt);
+    }
+    let mut feet = Vec::new();
+    for input in inputs.iter() {
+        for _ in 0..30 {
+            state.set_input(input.clone());
+            recorder.before_tick(&mut state);
+            state.step(&def, &level, sheet, Entity::new(0), dt);
+            recorder.after_tick(&state);
+            if !feet.contains(&state.boxes().foot) {
+                feet.push(state.boxes().foot);
+            }
+        }
+    }
+    assert_eq!(recorder.recording.inputs.len(), 150);
+    // the boxes followed the animation
+    assert!(feet.len() > 1);
+
+    let text =
+        ron::ser::to_string_pretty(&recorder.recording, ron::ser::PrettyConfig::default()).unwrap();
+    let recording = Recording::from_bytes(text.as_bytes()).unwrap();
+    let checksum = recorder.checksum();
+    assert_eq!(recording.checksum, checksum.0);
+
+    // the replay ignores whatever the character was doing before
+    let mut player = Replay::new(ReplayMode::Play, recording);
+    let mut state = CharacterState::default();
+    state.set_input(inputs[4].clone());
+    while !player.is_finished() {
+        player.before_tick(&mut state);
+        state.step(&def, &level, sheet, Entity::new(0), dt);
+        player.after_tick(&state);
+    }
+    assert_eq!(player.checksum(), checksum);
+
+    // a different map gives a different trajectory
+    let level = level::Level::from_shapes(Vec::new());
+    let mut player = Replay::new(ReplayMode::Play, recorder.recording);
+    let mut state = CharacterState::default();
+    while !player.is_finished() {
+        player.before_tick(&mut state);
+        state.step(&def, &level, sheet, Entity::new(0), dt);
+        player.after_tick(&state);
+    }
+    assert_ne!(player.checksum(), checksum);
+}
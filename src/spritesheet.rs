@@ -1,18 +1,55 @@
-use anyhow::Result;
+use super::ase;
+use anyhow::{anyhow, Context, Result};
 use bevy::{
-    asset::{AssetLoader, LoadedAsset},
+    asset::{AssetLoader, AssetPath, LoadedAsset},
     prelude::*,
+    render::texture::TextureFormat,
+    sprite::Rect,
 };
 use bevy_type_registry::TypeUuid;
 use serde::Deserialize;
 use std::{collections::HashMap, path::Path};
 
-mod aseprite {
+/// json hash / array sheet format, written by aseprite, texturepacker, free-tex-packer and others
+mod json {
+    use serde::{de, Deserialize, Deserializer};
+    use std::{collections::HashMap, fmt};
+
+    #[derive(Debug, Deserialize)]
+    pub struct Rect {
+        pub x: u32,
+        pub y: u32,
+        pub w: u32,
+        pub h: u32,
+    }
+    #[derive(Debug, Deserialize)]
+    pub struct Size {
+        pub w: u32,
+        pub h: u32,
+    }
+    #[derive(Debug, Deserialize)]
+    pub struct Point {
+        pub x: i32,
+        pub y: i32,
+    }
     #[derive(Debug, Deserialize)]
     #[serde(rename_all = "camelCase")]
     pub struct Frame {
-        // filename : String,
-        pub duration: u32,
+        /// the key of the frame in the hash format
+        #[serde(default)]
+        pub filename: String,
+        /// area of the (possibly trimmed) frame in the image, unrotated size
+        pub frame: Rect,
+        /// stored turned by 90 degrees clockwise in the image
+        #[serde(default)]
+        pub rotated: bool,
+        #[serde(default)]
+        pub trimmed: bool,
+        /// area of the trimmed frame within the untrimmed sprite
+        pub sprite_source_size: Rect,
+        pub source_size: Size,
+        /// only written by aseprite
+        pub duration: Option<u32>,
     }
     #[derive(Debug, Deserialize)]
     #[serde(rename_all = "camelCase")]
@@ -22,68 +59,518 @@ mod aseprite {
         pub to: u32,
         pub direction: String,
     }
+    /// frames are either an array or (aseprite's default "Hash" export) an object keyed by
+    /// filename; both keep the order of the document
+    #[derive(Debug)]
+    pub struct Frames(pub Vec<Frame>);
+
+    impl<'de> Deserialize<'de> for Frames {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Frames, D::Error> {
+            struct FramesVisitor;
+
+            impl<'de> de::Visitor<'de> for FramesVisitor {
+                type Value = Frames;
+
+                fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                    f.write_str("an array or a map of frames")
+                }
+
+                fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Frames, A::Error> {
+                    let mut frames = Vec::new();
+                    while let Some(frame) = seq.next_element()? {
+                        frames.push(frame);
+                    }
+                    Ok(Frames(frames))
+                }
+
+                fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<Frames, A::Error> {
+                    let mut frames = Vec::new();
+                    while let Some((filename, frame)) = map.next_entry::<String, Frame>()? {
+                        frames.push(Frame { filename, ..frame });
+                    }
+                    Ok(Frames(frames))
+                }
+            }
+
+            deserializer.deserialize_any(FramesVisitor)
+        }
+    }
+
+    #[derive(Debug, Deserialize)]
+    pub struct SliceKey {
+        pub frame: u32,
+        pub bounds: Rect,
+        pub pivot: Option<Point>,
+    }
+    #[derive(Debug, Deserialize)]
+    pub struct Slice {
+        pub name: String,
+        pub data: Option<String>,
+        pub keys: Vec<SliceKey>,
+    }
     #[derive(Debug, Deserialize)]
     #[serde(rename_all = "camelCase")]
     pub struct Meta {
+        #[serde(default)]
         pub app: String,
+        #[serde(default)]
         pub version: String,
         pub image: String,
+        #[serde(default)]
         pub format: String,
+        pub size: Size,
+        #[serde(default)]
         pub scale: String,
+        #[serde(default)]
         pub frame_tags: Vec<FrameTag>,
+        #[serde(default)]
+        pub slices: Vec<Slice>,
     }
 
     #[derive(Debug, Deserialize)]
     #[serde(rename_all = "camelCase")]
     pub struct Desc {
-        pub frames: Vec<Frame>,
+        pub frames: Frames,
         pub meta: Meta,
+        /// frame filenames per animation (texturepacker's pixi.js export)
+        #[serde(default)]
+        pub animations: HashMap<String, Vec<String>>,
+    }
+}
+
+/// playback direction of a frame tag
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LoopMode {
+    Forward,
+    Reverse,
+    PingPong,
+}
+
+impl LoopMode {
+    fn from_direction(direction: &str) -> LoopMode {
+        match direction {
+            "reverse" => LoopMode::Reverse,
+            "pingpong" => LoopMode::PingPong,
+            _ => LoopMode::Forward,
+        }
+    }
+
+    /// direction byte of a tag in an .aseprite file
+    fn from_ase(direction: u8) -> LoopMode {
+        match direction {
+            1 => LoopMode::Reverse,
+            2 | 3 => LoopMode::PingPong,
+            _ => LoopMode::Forward,
+        }
+    }
+}
+
+/// frames of a tag in playback order (tag ranges are inclusive in aseprite)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Animation {
+    pub frames: Vec<u32>,
+    /// duration of each entry of frames in ms
+    pub durations: Vec<u32>,
+    pub loop_mode: LoopMode,
+}
+
+impl Animation {
+    /// frames from..=to of the sheet
+    fn from_tag(from: u32, to: u32, loop_mode: LoopMode, durations: &[u32]) -> Animation {
+        let frames: Vec<u32> = (from..=to).collect();
+        Animation {
+            durations: frames
+                .iter()
+                .map(|f| durations.get(*f as usize).cloned().unwrap_or(100))
+                .collect(),
+            frames,
+            loop_mode,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SpriteFrame {
+    /// pixel area in the image
+    pub rect: Rect,
+    /// offset of the (trimmed) frame center from the center of the untrimmed sprite
+    pub offset: Vec2,
+    /// the image area holds the frame turned by 90 degrees clockwise
+    pub rotated: bool,
+}
+
+impl SpriteFrame {
+    fn from_json(frame: &json::Frame) -> SpriteFrame {
+        let f = &frame.frame;
+        let source = &frame.sprite_source_size;
+        // aseprite counts y downwards
+        let offset = Vec2::new(
+            source.x as f32 + source.w as f32 / 2.0 - frame.source_size.w as f32 / 2.0,
+            frame.source_size.h as f32 / 2.0 - source.y as f32 - source.h as f32 / 2.0,
+        );
+        let (w, h) = if frame.rotated {
+            (f.h, f.w)
+        } else {
+            (f.w, f.h)
+        };
+        SpriteFrame {
+            rect: Rect {
+                min: Vec2::new(f.x as f32, f.y as f32),
+                max: Vec2::new((f.x + w) as f32, (f.y + h) as f32),
+            },
+            offset,
+            rotated: frame.rotated,
+        }
+    }
+}
+
+/// slice bounds and pivot of a frame, in pixels of the untrimmed sprite (y pointing down)
+#[derive(Debug, Clone, PartialEq)]
+pub struct SliceKey {
+    pub frame: u32,
+    pub bounds: Rect,
+    /// relative to the top left of bounds
+    pub pivot: Option<Vec2>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Slice {
+    pub name: String,
+    /// user data of the slice
+    pub data: Option<String>,
+    /// sorted by frame
+    pub keys: Vec<SliceKey>,
+}
+
+impl Slice {
+    fn from_json(slice: &json::Slice) -> Slice {
+        let mut keys: Vec<SliceKey> = slice
+            .keys
+            .iter()
+            .map(|key| {
+                let b = &key.bounds;
+                SliceKey {
+                    frame: key.frame,
+                    bounds: Rect {
+                        min: Vec2::new(b.x as f32, b.y as f32),
+                        max: Vec2::new((b.x + b.w) as f32, (b.y + b.h) as f32),
+                    },
+                    pivot: key
+                        .pivot
+                        .as_ref()
+                        .map(|p| Vec2::new(p.x as f32, p.y as f32)),
+                }
+            })
+            .collect();
+        keys.sort_by_key(|key| key.frame);
+        Slice {
+            name: slice.name.clone(),
+            data: slice.data.clone(),
+            keys,
+        }
+    }
+
+    /// a key stays in effect until the next key, None before the first key
+    pub fn key_at(&self, frame: u32) -> Option<&SliceKey> {
+        self.keys.iter().rev().find(|key| key.frame <= frame)
     }
 }
 
 #[derive(Debug, TypeUuid)]
 #[uuid = "ab3a0ad8-6fbc-4528-a4a5-90e7bf3fa9e1"]
 pub struct Spritesheet {
+    /// image path relative to the spritesheet
     pub image: String,
-    pub ranges: HashMap<String, std::ops::Range<u32>>,
+    pub size: Vec2,
+    pub frames: Vec<SpriteFrame>,
+    pub animations: HashMap<String, Animation>,
+    pub slices: HashMap<String, Slice>,
     pub durations: Vec<u32>,
+    /// atlas built from the frames, loaded as the "atlas" label of the spritesheet
+    pub atlas: Handle<TextureAtlas>,
 }
 
 impl Spritesheet {
-    fn try_from_bytes(asset_path: &Path, bytes: Vec<u8>) -> Result<Spritesheet> {
-        let desc: aseprite::Desc = serde_json::from_slice(&bytes[..]).unwrap();
+    pub(crate) fn try_from_bytes(asset_path: &Path, bytes: Vec<u8>) -> Result<Spritesheet> {
+        let desc: json::Desc = serde_json::from_slice(&bytes[..])
+            .with_context(|| format!("{}: not a json spritesheet", asset_path.display()))?;
 
-        println!("desc: {:?}", desc);
-
-        let ranges = desc
-            .meta
-            .frame_tags
+        let importer = IMPORTERS
             .iter()
-            .map(|tag| (tag.name.clone(), tag.from..tag.to))
-            .collect();
-
-        let durations = desc.frames.iter().map(|f| f.duration).collect();
+            .find(|importer| importer.accepts(&desc.meta.app))
+            .unwrap_or(&IMPORTERS[IMPORTERS.len() - 1]);
+        let (durations, mut animations) = importer
+            .animations(&desc)
+            .with_context(|| format!("{}: {}", asset_path.display(), importer.name()))?;
+        if animations.is_empty() {
+            animations = animations_from_filenames(&desc, &durations);
+        }
 
         let spritesheet = Spritesheet {
-            image: "".into(),
-            ranges,
+            image: desc.meta.image.clone(),
+            size: Vec2::new(desc.meta.size.w as f32, desc.meta.size.h as f32),
+            frames: desc.frames.0.iter().map(SpriteFrame::from_json).collect(),
+            animations,
+            slices: desc
+                .meta
+                .slices
+                .iter()
+                .map(|slice| (slice.name.clone(), Slice::from_json(slice)))
+                .collect(),
             durations,
+            atlas: Handle::default(),
         };
 
         Ok(spritesheet)
     }
+
+    /// frames of the file laid out next to each other, see ase::AseFile::sheet_pixels
+    fn from_ase(ase: &ase::AseFile) -> Spritesheet {
+        let (w, h) = (ase.width as f32, ase.height as f32);
+        let durations: Vec<u32> = ase.frames.iter().map(|f| f.duration).collect();
+        let animations = ase
+            .tags
+            .iter()
+            .map(|tag| {
+                let loop_mode = LoopMode::from_ase(tag.direction);
+                let animation =
+                    Animation::from_tag(tag.from as u32, tag.to as u32, loop_mode, &durations);
+                (tag.name.clone(), animation)
+            })
+            .collect();
+        let slices = ase
+            .slices
+            .iter()
+            .map(|slice| {
+                let keys = slice
+                    .keys
+                    .iter()
+                    .map(|key| SliceKey {
+                        frame: key.frame,
+                        bounds: Rect {
+                            min: Vec2::new(key.x as f32, key.y as f32),
+                            max: Vec2::new(
+                                (key.x + key.w as i32) as f32,
+                                (key.y + key.h as i32) as f32,
+                            ),
+                        },
+                        pivot: key.pivot.map(|(x, y)| Vec2::new(x as f32, y as f32)),
+                    })
+                    .collect();
+                let slice = Slice {
+                    name: slice.name.clone(),
+                    data: None,
+                    keys,
+                };
+                (slice.name.clone(), slice)
+            })
+            .collect();
+
+        Spritesheet {
+            image: "".into(),
+            size: Vec2::new(w * ase.frames.len() as f32, h),
+            frames: (0..ase.frames.len())
+                .map(|i| SpriteFrame {
+                    rect: Rect {
+                        min: Vec2::new(w * i as f32, 0.0),
+                        max: Vec2::new(w * (i + 1) as f32, h),
+                    },
+                    offset: Vec2::zero(),
+                    rotated: false,
+                })
+                .collect(),
+            animations,
+            slices,
+            durations,
+            atlas: Handle::default(),
+        }
+    }
+
+    pub fn texture_atlas(&self, texture: Handle<Texture>) -> TextureAtlas {
+        let mut atlas = TextureAtlas::new_empty(texture, self.size);
+        for frame in self.frames.iter() {
+            atlas.add_texture(frame.rect);
+        }
+        atlas
+    }
+
+    /// rotation that shows the frame upright (rotated frames are stored turned clockwise)
+    pub fn frame_rotation(&self, index: u32) -> Quat {
+        match self.frames.get(index as usize) {
+            Some(frame) if frame.rotated => Quat::from_rotation_z(std::f32::consts::FRAC_PI_2),
+            _ => Quat::identity(),
+        }
+    }
+
+    /// offset to add to the sprite position so that trimmed frames line up
+    pub fn frame_offset(&self, index: u32) -> Vec2 {
+        self.frames
+            .get(index as usize)
+            .map_or(Vec2::zero(), |f| f.offset)
+    }
+}
+
+/// turns a json sheet description of one tool into frame durations and animations; frames,
+/// slices and the image are read the same way for all of them
+trait Importer: Sync {
+    fn name(&self) -> &'static str;
+    /// whether the sheet was written by this tool (meta.app)
+    fn accepts(&self, app: &str) -> bool;
+    fn animations(&self, desc: &json::Desc) -> Result<(Vec<u32>, HashMap<String, Animation>)>;
+}
+
+/// the last importer handles sheets of unknown tools
+static IMPORTERS: &[&dyn Importer] = &[&AsepriteImporter, &TexturePackerImporter];
+
+const DEFAULT_DURATION: u32 = 100;
+
+fn animations_from_tags(
+    tags: &[json::FrameTag],
+    durations: &[u32],
+) -> Result<HashMap<String, Animation>> {
+    tags.iter()
+        .map(|tag| {
+            if tag.from > tag.to || tag.to as usize >= durations.len() {
+                return Err(anyhow!(
+                    "tag '{}' frames {}..={} out of range ({} frames)",
+                    tag.name,
+                    tag.from,
+                    tag.to,
+                    durations.len()
+                ));
+            }
+            let loop_mode = LoopMode::from_direction(&tag.direction);
+            let animation = Animation::from_tag(tag.from, tag.to, loop_mode, durations);
+            Ok((tag.name.clone(), animation))
+        })
+        .collect()
+}
+
+/// "walk_01.png" -> ("walk", Some(1)), "idle.png" -> ("idle", None)
+fn split_frame_name(filename: &str) -> (&str, Option<u32>) {
+    let stem = match filename.rfind('.') {
+        Some(dot) if !filename[dot..].contains('/') => &filename[..dot],
+        _ => filename,
+    };
+    let name = stem.trim_end_matches(|c: char| c.is_ascii_digit());
+    let number = stem[name.len()..].parse().ok();
+    let name = name.trim_end_matches(|c: char| c == '_' || c == '-' || c == ' ' || c == '.');
+    if name.is_empty() {
+        (stem, None)
+    } else {
+        (name, number)
+    }
+}
+
+/// groups frames by filename without the trailing frame number, ordered by that number
+fn animations_from_filenames(desc: &json::Desc, durations: &[u32]) -> HashMap<String, Animation> {
+    let mut groups: HashMap<&str, Vec<(Option<u32>, u32)>> = HashMap::new();
+    for (index, frame) in desc.frames.0.iter().enumerate() {
+        let (name, number) = split_frame_name(&frame.filename);
+        groups.entry(name).or_default().push((number, index as u32));
+    }
+    groups
+        .into_iter()
+        .map(|(name, mut frames)| {
+            frames.sort();
+            let frames: Vec<u32> = frames.into_iter().map(|(_, index)| index).collect();
+            let animation = Animation {
+                durations: frames.iter().map(|f| durations[*f as usize]).collect(),
+                frames,
+                loop_mode: LoopMode::Forward,
+            };
+            (name.to_string(), animation)
+        })
+        .collect()
+}
+
+struct AsepriteImporter;
+
+impl Importer for AsepriteImporter {
+    fn name(&self) -> &'static str {
+        "aseprite"
+    }
+
+    fn accepts(&self, app: &str) -> bool {
+        app.contains("aseprite")
+    }
+
+    fn animations(&self, desc: &json::Desc) -> Result<(Vec<u32>, HashMap<String, Animation>)> {
+        let durations: Vec<u32> = desc
+            .frames
+            .0
+            .iter()
+            .map(|f| f.duration.unwrap_or(DEFAULT_DURATION))
+            .collect();
+        let animations = animations_from_tags(&desc.meta.frame_tags, &durations)?;
+        Ok((durations, animations))
+    }
+}
+
+/// texturepacker and free-tex-packer: no durations, rotated frames, animations listed by
+/// filename (pixi.js export) or inferred from the frame numbers
+struct TexturePackerImporter;
+
+impl Importer for TexturePackerImporter {
+    fn name(&self) -> &'static str {
+        "texturepacker"
+    }
+
+    fn accepts(&self, app: &str) -> bool {
+        app.contains("texturepacker") || app.contains("free-tex-packer")
+    }
+
+    fn animations(&self, desc: &json::Desc) -> Result<(Vec<u32>, HashMap<String, Animation>)> {
+        let durations: Vec<u32> = desc
+            .frames
+            .0
+            .iter()
+            .map(|f| f.duration.unwrap_or(DEFAULT_DURATION))
+            .collect();
+        let mut animations = animations_from_tags(&desc.meta.frame_tags, &durations)?;
+
+        let index: HashMap<&str, u32> = desc
+            .frames
+            .0
+            .iter()
+            .enumerate()
+            .map(|(i, f)| (f.filename.as_str(), i as u32))
+            .collect();
+        for (name, filenames) in desc.animations.iter() {
+            let frames = filenames
+                .iter()
+                .map(|filename| {
+                    index.get(filename.as_str()).cloned().ok_or_else(|| {
+                        anyhow!("animation '{}': unknown frame '{}'", name, filename)
+                    })
+                })
+                .collect::<Result<Vec<u32>>>()?;
+            let animation = Animation {
+                durations: frames.iter().map(|f| durations[*f as usize]).collect(),
+                frames,
+                loop_mode: LoopMode::Forward,
+            };
+            animations.insert(name.clone(), animation);
+        }
+        Ok((durations, animations))
+    }
 }
 
 #[derive(Default)]
 struct SpritesheetLoader {}
 
+/// loads .aseprite / .ase files directly, the composited frames become the "texture" label
+#[derive(Default)]
+struct AsepriteLoader {}
+
 #[derive(Default)]
 pub struct SpritesheetPlugin;
 
 impl Plugin for SpritesheetPlugin {
     fn build(&self, app: &mut AppBuilder) {
         app.add_asset::<Spritesheet>()
-            .init_asset_loader::<SpritesheetLoader>();
+            .init_asset_loader::<SpritesheetLoader>()
+            .init_asset_loader::<AsepriteLoader>();
     }
 }
 
@@ -95,14 +582,281 @@ impl AssetLoader for SpritesheetLoader {
     ) -> bevy::utils::BoxedFuture<'a, Result<(), anyhow::Error>> {
         Box::pin(async move {
             let path = load_context.path();
-            let map = Spritesheet::try_from_bytes(path, bytes.into())?;
-            load_context.set_default_asset(LoadedAsset::new(map));
+            let mut sheet = Spritesheet::try_from_bytes(path, bytes.into())?;
+
+            let image_path = path
+                .parent()
+                .unwrap_or_else(|| Path::new(""))
+                .join(&sheet.image);
+            let image_path = AssetPath::new(image_path, None);
+            let texture = load_context.get_handle(image_path.clone());
+            let atlas = sheet.texture_atlas(texture);
+            load_context
+                .set_labeled_asset("atlas", LoadedAsset::new(atlas).with_dependency(image_path));
+            sheet.atlas =
+                load_context.get_handle(AssetPath::new_ref(load_context.path(), Some("atlas")));
+
+            load_context.set_default_asset(LoadedAsset::new(sheet));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        // bevy picks loaders by the last extension only, so ".sheet.json" would still be "json"
+        static EXTENSIONS: &[&str] = &["sheet"];
+        EXTENSIONS
+    }
+}
+
+impl AssetLoader for AsepriteLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut bevy::asset::LoadContext,
+    ) -> bevy::utils::BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let path = load_context.path().to_path_buf();
+            let ase =
+                ase::AseFile::parse(bytes).map_err(|e| anyhow!("{}: {}", path.display(), e))?;
+            let mut sheet = Spritesheet::from_ase(&ase);
+
+            let texture = Texture::new(
+                sheet.size,
+                ase.sheet_pixels(),
+                TextureFormat::Rgba8UnormSrgb,
+            );
+            load_context.set_labeled_asset("texture", LoadedAsset::new(texture));
+            let texture = load_context.get_handle(AssetPath::new_ref(&path, Some("texture")));
+            let atlas = sheet.texture_atlas(texture);
+            load_context.set_labeled_asset("atlas", LoadedAsset::new(atlas));
+            sheet.atlas = load_context.get_handle(AssetPath::new_ref(&path, Some("atlas")));
+
+            if let Some(name) = path.file_name() {
+                sheet.image = format!("{}#texture", name.to_string_lossy());
+            }
+            load_context.set_default_asset(LoadedAsset::new(sheet));
             Ok(())
         })
     }
 
     fn extensions(&self) -> &[&str] {
-        static EXTENSIONS: &[&str] = &["json"];
+        static EXTENSIONS: &[&str] = &["aseprite", "ase"];
         EXTENSIONS
     }
 }
+
+#[test]
+fn test_ferris_animations() {
+    let path = Path::new("assets/ferris2.0.sheet");
+    let sheet = Spritesheet::try_from_bytes(path, std::fs::read(path).unwrap()).unwrap();
+    assert_eq!(sheet.image, "ferris2.0.png");
+    assert_eq!(sheet.durations.len(), 14);
+    assert_eq!(sheet.size, Vec2::new(224.0, 16.0));
+    assert_eq!(sheet.frames.len(), 14);
+    assert_eq!(sheet.frames[3].rect.min, Vec2::new(48.0, 0.0));
+    assert_eq!(sheet.frames[3].rect.max, Vec2::new(64.0, 16.0));
+    assert_eq!(sheet.frame_offset(3), Vec2::zero());
+
+    let walk = &sheet.animations["walk left"];
+    assert_eq!(walk.frames, vec![0, 1, 2, 3]);
+    assert_eq!(walk.durations, vec![128; 4]);
+    assert_eq!(walk.loop_mode, LoopMode::Forward);
+    assert_eq!(sheet.animations["walk right"].frames, vec![4, 5, 6, 7]);
+    assert_eq!(sheet.animations["jump left"].frames, vec![8]);
+    assert_eq!(sheet.animations["jump right"].frames, vec![9]);
+    assert_eq!(sheet.animations["wall slide left"].frames, vec![10]);
+    assert_eq!(sheet.animations["wall jump right"].frames, vec![13]);
+}
+
+#[test]
+fn test_tag_directions() {
+    let json = br#"{
+        "frames": [
+            { "frame": { "x": 0, "y": 0, "w": 16, "h": 16 }, "trimmed": false,
+              "spriteSourceSize": { "x": 0, "y": 0, "w": 16, "h": 16 },
+              "sourceSize": { "w": 16, "h": 16 }, "duration": 10 },
+            { "frame": { "x": 16, "y": 0, "w": 8, "h": 12 }, "trimmed": true,
+              "spriteSourceSize": { "x": 2, "y": 4, "w": 8, "h": 12 },
+              "sourceSize": { "w": 16, "h": 16 }, "duration": 20 },
+            { "frame": { "x": 24, "y": 0, "w": 16, "h": 16 }, "trimmed": false,
+              "spriteSourceSize": { "x": 0, "y": 0, "w": 16, "h": 16 },
+              "sourceSize": { "w": 16, "h": 16 }, "duration": 30 }
+        ],
+        "meta": {
+            "app": "", "version": "", "image": "", "format": "",
+            "size": { "w": 40, "h": 16 }, "scale": "1",
+            "frameTags": [
+                { "name": "a", "from": 0, "to": 2, "direction": "reverse" },
+                { "name": "b", "from": 1, "to": 2, "direction": "pingpong" }
+            ]
+        }
+    }"#;
+    let sheet = Spritesheet::try_from_bytes(Path::new("test.sheet"), json.to_vec()).unwrap();
+    assert_eq!(sheet.animations["a"].loop_mode, LoopMode::Reverse);
+    assert_eq!(sheet.animations["a"].durations, vec![10, 20, 30]);
+    // trimmed frame: 8x12 at (2, 4) in a 16x16 sprite
+    assert_eq!(sheet.frames[1].rect.min, Vec2::new(16.0, 0.0));
+    assert_eq!(sheet.frames[1].rect.max, Vec2::new(24.0, 12.0));
+    assert_eq!(sheet.frame_offset(1), Vec2::new(-2.0, -2.0));
+    assert_eq!(
+        sheet.animations["b"],
+        Animation {
+            frames: vec![1, 2],
+            durations: vec![20, 30],
+            loop_mode: LoopMode::PingPong,
+        }
+    );
+}
+
+#[test]
+fn test_hash_frames_and_slices() {
+    let json = br##"{
+        "frames": {
+            "s 2.aseprite": { "frame": { "x": 32, "y": 0, "w": 16, "h": 16 }, "trimmed": false,
+              "spriteSourceSize": { "x": 0, "y": 0, "w": 16, "h": 16 },
+              "sourceSize": { "w": 16, "h": 16 }, "duration": 30 },
+            "s 10.aseprite": { "frame": { "x": 0, "y": 0, "w": 16, "h": 16 }, "trimmed": false,
+              "spriteSourceSize": { "x": 0, "y": 0, "w": 16, "h": 16 },
+              "sourceSize": { "w": 16, "h": 16 }, "duration": 10 }
+        },
+        "meta": {
+            "app": "", "version": "", "image": "s.png", "format": "",
+            "size": { "w": 48, "h": 16 }, "scale": "1", "frameTags": [],
+            "slices": [
+                { "name": "hitbox", "color": "#0000ffff", "data": "solid", "keys": [
+                    { "frame": 1, "bounds": { "x": 4, "y": 2, "w": 8, "h": 12 },
+                      "pivot": { "x": 4, "y": 12 } },
+                    { "frame": 0, "bounds": { "x": 3, "y": 2, "w": 8, "h": 12 } }
+                ] }
+            ]
+        }
+    }"##;
+    let sheet = Spritesheet::try_from_bytes(Path::new("test.sheet"), json.to_vec()).unwrap();
+    // document order, not sorted by filename
+    assert_eq!(sheet.durations, vec![30, 10]);
+    assert_eq!(sheet.frames[0].rect.min, Vec2::new(32.0, 0.0));
+
+    let hitbox = &sheet.slices["hitbox"];
+    assert_eq!(hitbox.data.as_deref(), Some("solid"));
+    assert_eq!(hitbox.key_at(0).unwrap().bounds.min, Vec2::new(3.0, 2.0));
+    assert_eq!(hitbox.key_at(0).unwrap().pivot, None);
+    let key = hitbox.key_at(5).unwrap();
+    assert_eq!(key.frame, 1);
+    assert_eq!(key.bounds.max, Vec2::new(12.0, 14.0));
+    assert_eq!(key.pivot, Some(Vec2::new(4.0, 12.0)));
+}
+
+#[test]
+fn test_from_ase() {
+    let bytes = std::fs::read("assets/ferris2.0.aseprite").unwrap();
+    let sheet = Spritesheet::from_ase(&ase::AseFile::parse(&bytes).unwrap());
+    assert_eq!(sheet.size, Vec2::new(224.0, 16.0));
+    assert_eq!(sheet.frames.len(), 14);
+    assert_eq!(sheet.frames[9].rect.min, Vec2::new(144.0, 0.0));
+    assert_eq!(sheet.animations["walk right"].frames, vec![4, 5, 6, 7]);
+    assert_eq!(sheet.animations["wall slide left"].frames, vec![10]);
+    assert_eq!(sheet.animations["walk right"].durations, vec![100; 4]);
+}
+
+#[test]
+fn test_spritesheet_errors() {
+    let path = Path::new("broken.sheet");
+    let err = Spritesheet::try_from_bytes(path, b"{ \"frames\": [".to_vec()).unwrap_err();
+    assert!(err.to_string().starts_with("broken.sheet: "));
+    // some other json
+    assert!(Spritesheet::try_from_bytes(path, b"{ \"tiles\": [] }".to_vec()).is_err());
+
+    let json = br#"{
+        "frames": [
+            { "frame": { "x": 0, "y": 0, "w": 16, "h": 16 }, "trimmed": false,
+              "spriteSourceSize": { "x": 0, "y": 0, "w": 16, "h": 16 },
+              "sourceSize": { "w": 16, "h": 16 }, "duration": 10 }
+        ],
+        "meta": {
+            "app": "", "version": "", "image": "", "format": "",
+            "size": { "w": 16, "h": 16 }, "scale": "1",
+            "frameTags": [ { "name": "a", "from": 0, "to": 1, "direction": "forward" } ]
+        }
+    }"#;
+    let err = Spritesheet::try_from_bytes(path, json.to_vec()).unwrap_err();
+    assert!(format!("{:#}", err).contains("tag 'a'"));
+}
+
+#[test]
+fn test_split_frame_name() {
+    assert_eq!(split_frame_name("walk_01.png"), ("walk", Some(1)));
+    assert_eq!(split_frame_name("hero/jump-12"), ("hero/jump", Some(12)));
+    assert_eq!(
+        split_frame_name("ferris2.0 3.aseprite"),
+        ("ferris2.0", Some(3))
+    );
+    assert_eq!(split_frame_name("idle.png"), ("idle", None));
+    assert_eq!(split_frame_name("7.png"), ("7", None));
+}
+
+#[test]
+fn test_texturepacker_import() {
+    let json = br#"{
+        "frames": {
+            "walk_02.png": { "frame": { "x": 0, "y": 0, "w": 12, "h": 16 },
+              "rotated": true, "trimmed": true,
+              "spriteSourceSize": { "x": 2, "y": 0, "w": 12, "h": 16 },
+              "sourceSize": { "w": 16, "h": 16 }, "pivot": { "x": 0.5, "y": 0.5 } },
+            "walk_01.png": { "frame": { "x": 16, "y": 0, "w": 16, "h": 16 },
+              "rotated": false, "trimmed": false,
+              "spriteSourceSize": { "x": 0, "y": 0, "w": 16, "h": 16 },
+              "sourceSize": { "w": 16, "h": 16 }, "pivot": { "x": 0.5, "y": 0.5 } },
+            "idle.png": { "frame": { "x": 32, "y": 0, "w": 16, "h": 16 },
+              "rotated": false, "trimmed": false,
+              "spriteSourceSize": { "x": 0, "y": 0, "w": 16, "h": 16 },
+              "sourceSize": { "w": 16, "h": 16 }, "pivot": { "x": 0.5, "y": 0.5 } }
+        },
+        "meta": {
+            "app": "https://www.codeandweb.com/texturepacker", "version": "1.0",
+            "image": "hero.png", "format": "RGBA8888", "size": { "w": 48, "h": 16 },
+            "scale": "1", "smartupdate": ""
+        }
+    }"#;
+    let sheet = Spritesheet::try_from_bytes(Path::new("hero.sheet"), json.to_vec()).unwrap();
+    assert_eq!(sheet.image, "hero.png");
+    // rotated: the 12x16 frame takes 16x12 pixels in the image
+    assert!(sheet.frames[0].rotated);
+    assert_eq!(sheet.frames[0].rect.max, Vec2::new(16.0, 12.0));
+    assert_eq!(sheet.frame_offset(0), Vec2::new(0.0, 0.0));
+    assert_eq!(sheet.frame_rotation(1), Quat::identity());
+
+    // inferred from the filenames, ordered by number instead of document order
+    assert_eq!(sheet.animations["walk"].frames, vec![1, 0]);
+    assert_eq!(sheet.animations["walk"].durations, vec![100, 100]);
+    assert_eq!(sheet.animations["idle"].frames, vec![2]);
+}
+
+#[test]
+fn test_free_tex_packer_animations() {
+    let json = br#"{
+        "frames": [
+            { "filename": "a", "frame": { "x": 0, "y": 0, "w": 8, "h": 8 },
+              "rotated": false, "trimmed": false,
+              "spriteSourceSize": { "x": 0, "y": 0, "w": 8, "h": 8 },
+              "sourceSize": { "w": 8, "h": 8 } },
+            { "filename": "b", "frame": { "x": 8, "y": 0, "w": 8, "h": 8 },
+              "rotated": false, "trimmed": false,
+              "spriteSourceSize": { "x": 0, "y": 0, "w": 8, "h": 8 },
+              "sourceSize": { "w": 8, "h": 8 } }
+        ],
+        "animations": { "blink": ["b", "a", "b"] },
+        "meta": {
+            "app": "http://github.com/odrick/free-tex-packer", "version": "0.6.7",
+            "image": "fx.png", "size": { "w": 16, "h": 8 }
+        }
+    }"#;
+    let sheet = Spritesheet::try_from_bytes(Path::new("fx.sheet"), json.to_vec()).unwrap();
+    assert_eq!(sheet.animations.len(), 1);
+    assert_eq!(sheet.animations["blink"].frames, vec![1, 0, 1]);
+
+    let broken = String::from_utf8(json.to_vec())
+        .unwrap()
+        .replace(r#""a", "b""#, r#""a", "c""#);
+    let err = Spritesheet::try_from_bytes(Path::new("fx.sheet"), broken.into_bytes()).unwrap_err();
+    assert!(format!("{:#}", err).contains("unknown frame 'c'"));
+}
@@ -0,0 +1,93 @@
+use bevy::prelude::*;
+
+/// ticks per second of the simulation
+pub const TICK_RATE: f32 = 120.0;
+
+/// the simulation advances in ticks of a fixed length so it does not depend on the frame rate:
+/// the time of each frame goes into an accumulator which is used up in whole ticks, the
+/// remainder interpolates the rendering between the last two ticks
+#[derive(Debug)]
+pub struct FixedTimestep {
+    /// seconds per tick
+    pub step: f32,
+    /// upper bound of ticks per frame, after a long stall the simulation drops time instead of
+    /// catching up
+    pub max_ticks: u32,
+    accumulator: f64,
+    /// ticks to run in this frame
+    ticks: u32,
+    /// ticks since the start
+    pub tick: u64,
+}
+
+impl Default for FixedTimestep {
+    fn default() -> Self {
+        FixedTimestep::new(TICK_RATE)
+    }
+}
+
+impl FixedTimestep {
+    pub fn new(rate: f32) -> Self {
+        FixedTimestep {
+            step: 1.0 / rate,
+            max_ticks: 8,
+            accumulator: 0.0,
+            ticks: 0,
+            tick: 0,
+        }
+    }
+
+    /// adds the time of a frame, returns the number of ticks to run
+    pub fn advance(&mut self, delta: f64) -> u32 {
+        let step = self.step as f64;
+        self.accumulator += delta;
+        self.ticks = (self.accumulator / step) as u32;
+        self.accumulator -= self.ticks as f64 * step;
+        if self.ticks > self.max_ticks {
+            self.ticks = self.max_ticks;
+            self.accumulator = 0.0;
+        }
+        self.tick += self.ticks as u64;
+        self.ticks
+    }
+
+    pub fn ticks(&self) -> u32 {
+        self.ticks
+    }
+
+    /// progress towards the next tick in 0..1
+    pub fn alpha(&self) -> f32 {
+        (self.accumulator / self.step as f64) as f32
+    }
+}
+
+pub fn advance_timestep(time: Res<Time>, mut timestep: ResMut<FixedTimestep>) {
+    timestep.advance(time.delta_seconds_f64);
+}
+
+pub struct TimestepPlugin;
+
+impl Plugin for TimestepPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<FixedTimestep>()
+            .add_system_to_stage(stage::PRE_UPDATE, advance_timestep.system());
+    }
+}
+
+#[test]
+fn test_fixed_timestep() {
+    let mut timestep = FixedTimestep::new(120.0);
+    assert_eq!(timestep.advance(1.0 / 60.0), 2);
+    assert!(timestep.alpha() < 1e-3);
+
+    // frames shorter than a tick carry over
+    assert_eq!(timestep.advance(0.005), 0);
+    assert_relative_eq!(timestep.alpha(), 0.6, epsilon = 1e-4);
+    assert_eq!(timestep.advance(0.005), 1);
+    assert_relative_eq!(timestep.alpha(), 0.2, epsilon = 1e-4);
+
+    // a stall is cut off
+    assert_eq!(timestep.advance(1.0), 8);
+    assert_eq!(timestep.alpha(), 0.0);
+    assert_eq!(timestep.tick, 11);
+}